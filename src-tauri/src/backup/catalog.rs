@@ -0,0 +1,529 @@
+//! Manifest Catalog - SQLite index of manifests, the files they hold and where they are stored
+//! Answers listing and lookup questions without loading whole manifest files
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::engine::BackupError;
+use super::manifest::{
+    BackupManifest, CloudLocation, FileEntry, ManifestSummary, SkippedFile, SourceLabel,
+};
+use super::rawpath::{decode_path, encode_path};
+use super::search::FileSearchQuery;
+use super::set::BackupMode;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS manifests (
+    id TEXT PRIMARY KEY,
+    backup_set_id TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    parent_id TEXT,
+    file_count INTEGER NOT NULL,
+    total_size INTEGER NOT NULL,
+    compressed_size INTEGER NOT NULL,
+    retention_until TEXT,
+    archive_path TEXT,
+    superseded_by TEXT,
+    pinned INTEGER NOT NULL DEFAULT 0,
+    label TEXT,
+    sources TEXT NOT NULL,
+    skipped_files TEXT NOT NULL,
+    merged_from TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS manifests_by_set ON manifests (backup_set_id, created_at);
+CREATE INDEX IF NOT EXISTS manifests_by_parent ON manifests (parent_id);
+CREATE TABLE IF NOT EXISTS files (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL UNIQUE
+);
+CREATE TABLE IF NOT EXISTS versions (
+    manifest_id TEXT NOT NULL REFERENCES manifests (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    file_id INTEGER NOT NULL REFERENCES files (id),
+    relative_path TEXT NOT NULL,
+    source_label TEXT,
+    size INTEGER NOT NULL,
+    hash TEXT NOT NULL,
+    modified TEXT NOT NULL,
+    backed_up_at TEXT,
+    PRIMARY KEY (manifest_id, position)
+);
+CREATE INDEX IF NOT EXISTS versions_by_file ON versions (file_id);
+CREATE INDEX IF NOT EXISTS versions_by_hash ON versions (hash);
+CREATE TABLE IF NOT EXISTS cloud_locations (
+    manifest_id TEXT PRIMARY KEY REFERENCES manifests (id) ON DELETE CASCADE,
+    provider TEXT NOT NULL,
+    file_id TEXT NOT NULL,
+    folder_id TEXT NOT NULL,
+    chunks TEXT NOT NULL
+);
+";
+
+/// Columns read by `summary_from_row`, in order
+const SUMMARY_COLUMNS: &str = "m.id, m.backup_set_id, m.created_at, m.mode, m.parent_id, \
+     m.file_count, m.total_size, m.compressed_size, \
+     EXISTS (SELECT 1 FROM cloud_locations c WHERE c.manifest_id = m.id), \
+     m.superseded_by, m.pinned, m.label";
+
+/// Meta key set once the JSON manifests of older versions have been imported
+const JSON_IMPORTED: &str = "json_imported";
+const LAST_UPDATED: &str = "last_updated";
+
+/// A manifest's catalog record: everything except its file list and deletions.
+#[derive(Debug, Clone)]
+pub struct CatalogEntry {
+    pub summary: ManifestSummary,
+    pub retention_until: Option<DateTime<Utc>>,
+    pub archive_path: Option<PathBuf>,
+    pub cloud_location: Option<CloudLocation>,
+    pub sources: Vec<SourceLabel>,
+    pub skipped_files: Vec<SkippedFile>,
+    pub merged_from: Vec<String>,
+}
+
+/// One stored version of a file together with the manifest that holds it.
+#[derive(Debug, Clone)]
+pub struct CatalogVersion {
+    pub file: FileEntry,
+    pub manifest: ManifestSummary,
+    pub archive_path: Option<PathBuf>,
+    pub cloud_location: Option<CloudLocation>,
+}
+
+fn mode_name(mode: BackupMode) -> &'static str {
+    match mode {
+        BackupMode::Full => "full",
+        BackupMode::Incremental => "incremental",
+        BackupMode::Differential => "differential",
+    }
+}
+
+fn parse_mode(name: &str) -> BackupMode {
+    match name {
+        "incremental" => BackupMode::Incremental,
+        "differential" => BackupMode::Differential,
+        _ => BackupMode::Full,
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, BackupError> {
+    serde_json::to_string(value).map_err(|e| BackupError::Manifest(e.to_string()))
+}
+
+/// Parse a JSON column inside a row mapper.
+fn from_json<T: serde::de::DeserializeOwned>(index: usize, json: &str) -> rusqlite::Result<T> {
+    serde_json::from_str(json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+fn summary_from_row(row: &Row) -> rusqlite::Result<ManifestSummary> {
+    Ok(ManifestSummary {
+        id: row.get(0)?,
+        backup_set_id: row.get(1)?,
+        created_at: row.get(2)?,
+        mode: parse_mode(&row.get::<_, String>(3)?),
+        parent_id: row.get(4)?,
+        file_count: row.get::<_, i64>(5)? as u64,
+        total_size: row.get::<_, i64>(6)? as u64,
+        compressed_size: row.get::<_, i64>(7)? as u64,
+        is_uploaded: row.get(8)?,
+        superseded_by: row.get(9)?,
+        pinned: row.get(10)?,
+        label: row.get(11)?,
+    })
+}
+
+/// Reads the cloud location columns starting at `start`.
+fn cloud_location_from_row(row: &Row, start: usize) -> rusqlite::Result<Option<CloudLocation>> {
+    let Some(provider) = row.get::<_, Option<String>>(start)? else {
+        return Ok(None);
+    };
+    Ok(Some(CloudLocation {
+        provider,
+        file_id: row.get(start + 1)?,
+        folder_id: row.get(start + 2)?,
+        chunks: from_json(start + 3, &row.get::<_, String>(start + 3)?)?,
+    }))
+}
+
+pub struct Catalog {
+    conn: Connection,
+}
+
+impl Catalog {
+    /// Open the catalog at `path`, creating the database and its tables if needed.
+    pub fn open(path: &Path) -> Result<Self, BackupError> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    fn meta(&self, key: &str) -> Result<Option<String>, BackupError> {
+        Ok(self
+            .conn
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    fn set_meta(tx: &Transaction, key: &str, value: &str) -> Result<(), BackupError> {
+        tx.execute(
+            "INSERT INTO meta (key, value) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    pub fn json_imported(&self) -> Result<bool, BackupError> {
+        Ok(self.meta(JSON_IMPORTED)?.is_some())
+    }
+
+    /// Replace the whole catalog with `manifests` in one transaction. Also used for the
+    /// one-time import of manifests written before the catalog existed, which it marks done.
+    pub fn rebuild(&mut self, manifests: &[BackupManifest]) -> Result<(), BackupError> {
+        let tx = self.conn.transaction()?;
+        tx.execute_batch(
+            "DELETE FROM versions; DELETE FROM cloud_locations; DELETE FROM manifests;
+             DELETE FROM files;",
+        )?;
+        for manifest in manifests {
+            Self::upsert_in(&tx, manifest)?;
+        }
+        Self::set_meta(&tx, JSON_IMPORTED, &Utc::now().to_rfc3339())?;
+        Self::set_meta(&tx, LAST_UPDATED, &Utc::now().to_rfc3339())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn last_updated(&self) -> Result<Option<DateTime<Utc>>, BackupError> {
+        Ok(self
+            .meta(LAST_UPDATED)?
+            .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+            .map(|t| t.with_timezone(&Utc)))
+    }
+
+    /// Insert or update a manifest's record.
+    pub fn upsert(&mut self, manifest: &BackupManifest) -> Result<(), BackupError> {
+        let tx = self.conn.transaction()?;
+        Self::upsert_in(&tx, manifest)?;
+        Self::set_meta(&tx, LAST_UPDATED, &Utc::now().to_rfc3339())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn upsert_in(tx: &Transaction, manifest: &BackupManifest) -> Result<(), BackupError> {
+        let known: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM manifests WHERE id = ?1)",
+            [&manifest.id],
+            |row| row.get(0),
+        )?;
+
+        tx.execute(
+            "INSERT INTO manifests (id, backup_set_id, created_at, mode, parent_id, file_count,
+                 total_size, compressed_size, retention_until, archive_path, superseded_by,
+                 pinned, label, sources, skipped_files, merged_from)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+             ON CONFLICT (id) DO UPDATE SET
+                 retention_until = excluded.retention_until,
+                 archive_path = excluded.archive_path,
+                 superseded_by = excluded.superseded_by,
+                 pinned = excluded.pinned,
+                 label = excluded.label,
+                 merged_from = excluded.merged_from",
+            params![
+                manifest.id,
+                manifest.backup_set_id,
+                manifest.created_at,
+                mode_name(manifest.mode),
+                manifest.parent_id,
+                manifest.files.len() as i64,
+                manifest.total_size as i64,
+                manifest.compressed_size as i64,
+                manifest.retention_until,
+                manifest.archive_path.as_deref().map(encode_path),
+                manifest.superseded_by,
+                manifest.pinned,
+                manifest.label,
+                to_json(&manifest.sources)?,
+                to_json(&manifest.skipped_files)?,
+                to_json(&manifest.merged_from)?,
+            ],
+        )?;
+
+        match &manifest.cloud_location {
+            Some(location) => {
+                tx.execute(
+                    "INSERT INTO cloud_locations (manifest_id, provider, file_id, folder_id, chunks)
+                     VALUES (?1, ?2, ?3, ?4, ?5)
+                     ON CONFLICT (manifest_id) DO UPDATE SET
+                         provider = excluded.provider,
+                         file_id = excluded.file_id,
+                         folder_id = excluded.folder_id,
+                         chunks = excluded.chunks",
+                    params![
+                        manifest.id,
+                        location.provider,
+                        location.file_id,
+                        location.folder_id,
+                        to_json(&location.chunks)?,
+                    ],
+                )?;
+            }
+            None => {
+                tx.execute(
+                    "DELETE FROM cloud_locations WHERE manifest_id = ?1",
+                    [&manifest.id],
+                )?;
+            }
+        }
+
+        // A manifest's file list never changes once saved, so only new manifests add versions
+        if !known {
+            let mut file_id = tx.prepare_cached(
+                "INSERT INTO files (path) VALUES (?1)
+                 ON CONFLICT (path) DO UPDATE SET path = excluded.path
+                 RETURNING id",
+            )?;
+            let mut version = tx.prepare_cached(
+                "INSERT INTO versions (manifest_id, position, file_id, relative_path,
+                     source_label, size, hash, modified, backed_up_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+            for (position, file) in manifest.files.iter().enumerate() {
+                let id: i64 = file_id.query_row([encode_path(&file.path)], |row| row.get(0))?;
+                version.execute(params![
+                    manifest.id,
+                    position as i64,
+                    id,
+                    encode_path(&file.relative_path),
+                    file.source_label,
+                    file.size as i64,
+                    file.hash,
+                    file.modified,
+                    file.backed_up_at,
+                ])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop a manifest's record, its versions and any paths no other manifest refers to.
+    pub fn remove(&mut self, id: &str) -> Result<(), BackupError> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM manifests WHERE id = ?1", [id])?;
+        tx.execute(
+            "DELETE FROM files
+             WHERE NOT EXISTS (SELECT 1 FROM versions v WHERE v.file_id = files.id)",
+            [],
+        )?;
+        Self::set_meta(&tx, LAST_UPDATED, &Utc::now().to_rfc3339())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn query_summaries(
+        &self,
+        filter: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<ManifestSummary>, BackupError> {
+        let sql = format!("SELECT {} FROM manifests m {}", SUMMARY_COLUMNS, filter);
+        let mut stmt = self.conn.prepare_cached(&sql)?;
+        let summaries = stmt
+            .query_map(params, summary_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(summaries)
+    }
+
+    /// Every manifest, oldest first.
+    pub fn summaries(&self) -> Result<Vec<ManifestSummary>, BackupError> {
+        self.query_summaries("ORDER BY m.created_at", [])
+    }
+
+    /// Manifests of one set, oldest first.
+    pub fn summaries_for_set(
+        &self,
+        backup_set_id: &str,
+    ) -> Result<Vec<ManifestSummary>, BackupError> {
+        self.query_summaries(
+            "WHERE m.backup_set_id = ?1 ORDER BY m.created_at",
+            [backup_set_id],
+        )
+    }
+
+    pub fn summary(&self, id: &str) -> Result<Option<ManifestSummary>, BackupError> {
+        Ok(self.query_summaries("WHERE m.id = ?1", [id])?.pop())
+    }
+
+    /// Newest manifest of a set, optionally only among full backups.
+    pub fn latest(
+        &self,
+        backup_set_id: &str,
+        mode: Option<BackupMode>,
+    ) -> Result<Option<ManifestSummary>, BackupError> {
+        let summaries = match mode {
+            Some(mode) => self.query_summaries(
+                "WHERE m.backup_set_id = ?1 AND m.mode = ?2 ORDER BY m.created_at DESC LIMIT 1",
+                [backup_set_id, mode_name(mode)],
+            )?,
+            None => self.query_summaries(
+                "WHERE m.backup_set_id = ?1 ORDER BY m.created_at DESC LIMIT 1",
+                [backup_set_id],
+            )?,
+        };
+        Ok(summaries.into_iter().next())
+    }
+
+    /// Ids of the manifests built directly on `id`.
+    pub fn dependents(&self, id: &str) -> Result<Vec<String>, BackupError> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT id FROM manifests WHERE parent_id = ?1 ORDER BY created_at")?;
+        let ids = stmt
+            .query_map([id], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(ids)
+    }
+
+    pub fn entry(&self, id: &str) -> Result<Option<CatalogEntry>, BackupError> {
+        let sql = format!(
+            "SELECT {}, m.retention_until, m.archive_path, m.sources, m.skipped_files,
+                 m.merged_from, c.provider, c.file_id, c.folder_id, c.chunks
+             FROM manifests m LEFT JOIN cloud_locations c ON c.manifest_id = m.id
+             WHERE m.id = ?1",
+            SUMMARY_COLUMNS
+        );
+        let entry = self
+            .conn
+            .query_row(&sql, [id], |row| {
+                Ok(CatalogEntry {
+                    summary: summary_from_row(row)?,
+                    retention_until: row.get(12)?,
+                    archive_path: row.get::<_, Option<String>>(13)?.map(|p| decode_path(&p)),
+                    sources: from_json(14, &row.get::<_, String>(14)?)?,
+                    skipped_files: from_json(15, &row.get::<_, String>(15)?)?,
+                    merged_from: from_json(16, &row.get::<_, String>(16)?)?,
+                    cloud_location: cloud_location_from_row(row, 17)?,
+                })
+            })
+            .optional()?;
+        Ok(entry)
+    }
+
+    /// Files `offset..offset + limit` of a manifest, in manifest order.
+    pub fn files(
+        &self,
+        id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<FileEntry>, BackupError> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT f.path, v.relative_path, v.size, v.hash, v.modified, v.backed_up_at,
+                 v.source_label
+             FROM versions v JOIN files f ON f.id = v.file_id
+             WHERE v.manifest_id = ?1
+             ORDER BY v.position LIMIT ?2 OFFSET ?3",
+        )?;
+        let files = stmt
+            .query_map(params![id, limit as i64, offset as i64], |row| {
+                file_from_row(row, 0)
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(files)
+    }
+
+    /// Stored versions matching the size, date, set and hash criteria of `query`.
+    /// Name, path and pattern criteria are left to the caller, which folds case properly.
+    pub fn find_versions(
+        &self,
+        query: &FileSearchQuery,
+    ) -> Result<Vec<CatalogVersion>, BackupError> {
+        let mut conditions = Vec::new();
+        let mut values: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        let mut add = |condition: &str, value: Box<dyn rusqlite::ToSql>| {
+            values.push(value);
+            conditions.push(condition.replace('?', &format!("?{}", values.len())));
+        };
+
+        if let Some(hash) = query.hash.as_ref().filter(|h| !h.is_empty()) {
+            add("v.hash = ?", Box::new(hash.to_lowercase()));
+        }
+        if let Some(id) = &query.backup_set_id {
+            add("m.backup_set_id = ?", Box::new(id.clone()));
+        }
+        if let Some(min) = query.min_size {
+            add("v.size >= ?", Box::new(min as i64));
+        }
+        if let Some(max) = query.max_size {
+            add("v.size <= ?", Box::new(max as i64));
+        }
+        if let Some(t) = query.modified_after {
+            add("v.modified >= ?", Box::new(t));
+        }
+        if let Some(t) = query.modified_before {
+            add("v.modified <= ?", Box::new(t));
+        }
+        if let Some(t) = query.backed_up_after {
+            add("m.created_at >= ?", Box::new(t));
+        }
+        if let Some(t) = query.backed_up_before {
+            add("m.created_at <= ?", Box::new(t));
+        }
+
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let sql = format!(
+            "SELECT {}, m.archive_path, c.provider, c.file_id, c.folder_id, c.chunks,
+                 f.path, v.relative_path, v.size, v.hash, v.modified, v.backed_up_at,
+                 v.source_label
+             FROM versions v
+             JOIN files f ON f.id = v.file_id
+             JOIN manifests m ON m.id = v.manifest_id
+             LEFT JOIN cloud_locations c ON c.manifest_id = m.id
+             {}",
+            SUMMARY_COLUMNS, filter
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let versions = stmt
+            .query_map(rusqlite::params_from_iter(values.iter()), |row| {
+                Ok(CatalogVersion {
+                    manifest: summary_from_row(row)?,
+                    archive_path: row.get::<_, Option<String>>(12)?.map(|p| decode_path(&p)),
+                    cloud_location: cloud_location_from_row(row, 13)?,
+                    file: file_from_row(row, 17)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(versions)
+    }
+}
+
+/// Reads path, relative path, size, hash, modified, backed-up time and source label
+/// starting at `start`.
+fn file_from_row(row: &Row, start: usize) -> rusqlite::Result<FileEntry> {
+    Ok(FileEntry {
+        path: decode_path(&row.get::<_, String>(start)?),
+        relative_path: decode_path(&row.get::<_, String>(start + 1)?),
+        size: row.get::<_, i64>(start + 2)? as u64,
+        hash: row.get(start + 3)?,
+        modified: row.get(start + 4)?,
+        backed_up_at: row.get(start + 5)?,
+        source_label: row.get(start + 6)?,
+    })
+}
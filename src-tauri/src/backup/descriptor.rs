@@ -0,0 +1,194 @@
+//! Archive Descriptor - The manifest and set details stored inside every archive
+//! Lets an archive be indexed, verified and restored without the manifest store
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+use super::engine::BackupError;
+use super::manifest::{BackupManifest, SourceLabel};
+use super::rawpath::entry_path;
+use super::zipstream::EntryWriter;
+
+/// Reserved entry holding the descriptor; never a backed up file, as those are stored
+/// under their source label.
+pub const DESCRIPTOR_ENTRY: &str = ".sentry/archive.json";
+
+/// Version of the descriptor written by this build
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveDescriptor {
+    pub format_version: u32,
+    /// Name of the set when the archive was written
+    pub backup_set_name: String,
+    pub sources: Vec<SourceLabel>,
+    /// Manifest of the version, without where its archive ended up
+    pub manifest: BackupManifest,
+}
+
+impl ArchiveDescriptor {
+    pub fn new(backup_set_name: &str, manifest: &BackupManifest) -> Self {
+        let mut manifest = manifest.clone();
+        manifest.archive_path = None;
+        manifest.cloud_location = None;
+        manifest.compressed_size = 0;
+
+        Self {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            backup_set_name: backup_set_name.to_string(),
+            sources: manifest.sources.clone(),
+            manifest,
+        }
+    }
+
+    /// Add the descriptor as the last entry of `zip`.
+    pub fn write_to<Z: EntryWriter>(&self, zip: &mut Z) -> Result<(), BackupError> {
+        let json =
+            serde_json::to_vec_pretty(self).map_err(|e| BackupError::Manifest(e.to_string()))?;
+        zip.start_entry(DESCRIPTOR_ENTRY, None, false)?;
+        zip.write_all(&json)?;
+        Ok(())
+    }
+
+    /// The descriptor of an open archive, or `None` for archives written before descriptors.
+    pub fn read_from<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+    ) -> Result<Option<Self>, BackupError> {
+        let entry = match archive.by_name(DESCRIPTOR_ENTRY) {
+            Ok(entry) => entry,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let stored: StoredDescriptor =
+            serde_json::from_reader(entry).map_err(|e| BackupError::Manifest(e.to_string()))?;
+
+        if stored.format_version > ARCHIVE_FORMAT_VERSION {
+            return Err(BackupError::Manifest(format!(
+                "Archive uses format version {}, but this version of Sentry reads up to version {}. Update Sentry to open it",
+                stored.format_version, ARCHIVE_FORMAT_VERSION
+            )));
+        }
+        Ok(Some(Self {
+            format_version: stored.format_version,
+            backup_set_name: stored.backup_set_name,
+            sources: stored.sources,
+            // The embedded manifest may be of an older schema; upgrade it like a stored one
+            manifest: BackupManifest::from_value(stored.manifest)?,
+        }))
+    }
+
+    /// Open the archive at `path` and read its descriptor. The manifest records `path` as
+    /// its archive.
+    pub fn read(path: &Path) -> Result<Option<Self>, BackupError> {
+        let file = File::open(path)?;
+        let compressed_size = file.metadata()?.len();
+        let mut archive = ZipArchive::new(file)?;
+        Ok(Self::read_from(&mut archive)?.map(|mut descriptor| {
+            descriptor.manifest.archive_path = Some(path.to_path_buf());
+            descriptor.manifest.compressed_size = compressed_size;
+            descriptor
+        }))
+    }
+}
+
+/// The descriptor as written, before its manifest is upgraded to the current schema.
+#[derive(Deserialize)]
+struct StoredDescriptor {
+    format_version: u32,
+    backup_set_name: String,
+    #[serde(default)]
+    sources: Vec<SourceLabel>,
+    manifest: Value,
+}
+
+/// Result of checking an archive against its embedded manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveVerification {
+    pub archive_path: PathBuf,
+    pub manifest_id: String,
+    pub backup_set_name: String,
+    pub files_checked: u64,
+    pub bytes_checked: u64,
+    /// Files the manifest lists that are not in the archive
+    pub missing: Vec<String>,
+    /// Entries whose contents do not match the recorded size and hash
+    pub corrupt: Vec<String>,
+}
+
+impl ArchiveVerification {
+    pub fn is_valid(&self) -> bool {
+        self.missing.is_empty() && self.corrupt.is_empty()
+    }
+}
+
+/// Read every file of a self-describing archive and compare it with the manifest inside.
+pub fn verify_archive(path: &Path) -> Result<ArchiveVerification, BackupError> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let descriptor = ArchiveDescriptor::read_from(&mut archive)?.ok_or_else(|| {
+        BackupError::Manifest(format!(
+            "{} has no embedded manifest; it was written by an older version of Sentry",
+            path.display()
+        ))
+    })?;
+
+    let mut verification = ArchiveVerification {
+        archive_path: path.to_path_buf(),
+        manifest_id: descriptor.manifest.id.clone(),
+        backup_set_name: descriptor.backup_set_name.clone(),
+        files_checked: 0,
+        bytes_checked: 0,
+        missing: vec![],
+        corrupt: vec![],
+    };
+    let mut expected: HashMap<PathBuf, (u64, &str)> = descriptor
+        .manifest
+        .files
+        .iter()
+        .map(|f| (f.archived_path(), (f.size, f.hash.as_str())))
+        .collect();
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let path = entry_path(entry.name(), entry.extra_data());
+        let Some((size, hash)) = expected.remove(&path) else {
+            continue;
+        };
+
+        let mut hasher = Sha256::new();
+        let mut buffer = [0u8; 8192];
+        let mut read = 0u64;
+        let intact = loop {
+            match entry.read(&mut buffer) {
+                Ok(0) => break true,
+                Ok(n) => {
+                    hasher.update(&buffer[..n]);
+                    read += n as u64;
+                }
+                // A failed CRC check or a damaged stream
+                Err(_) => break false,
+            }
+        };
+        if !intact || read != size || format!("{:x}", hasher.finalize()) != hash {
+            verification
+                .corrupt
+                .push(path.to_string_lossy().to_string());
+        }
+        verification.files_checked += 1;
+        verification.bytes_checked += read;
+    }
+    // Whatever was not matched by an entry is not in the archive
+    verification.missing = expected
+        .keys()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+
+    verification.missing.sort();
+    verification.corrupt.sort();
+    Ok(verification)
+}
@@ -0,0 +1,167 @@
+//! Version Diff - Compares the file state of two backup versions of a set
+//! Each side is the cumulative state of its chain, so incremental versions compare fully
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use super::engine::BackupError;
+use super::manifest::{chain_state, FileEntry, ManifestManager};
+use super::rawpath::serde_path;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffEntry {
+    #[serde(with = "serde_path")]
+    pub path: PathBuf,
+    pub size: u64,
+    pub hash: String,
+}
+
+impl DiffEntry {
+    fn from_file(file: &FileEntry) -> Self {
+        Self {
+            path: file.path.clone(),
+            size: file.size,
+            hash: file.hash.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModifiedEntry {
+    #[serde(with = "serde_path")]
+    pub path: PathBuf,
+    pub old_size: u64,
+    pub new_size: u64,
+    pub size_delta: i64,
+    pub old_hash: String,
+    pub new_hash: String,
+}
+
+/// A file that disappeared from one path and appeared with the same content at another.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenamedEntry {
+    #[serde(with = "serde_path")]
+    pub from: PathBuf,
+    #[serde(with = "serde_path")]
+    pub to: PathBuf,
+    pub size: u64,
+    pub hash: String,
+}
+
+/// Changes from version `from_id` to version `to_id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestDiff {
+    pub from_id: String,
+    pub to_id: String,
+    pub added: Vec<DiffEntry>,
+    pub removed: Vec<DiffEntry>,
+    pub modified: Vec<ModifiedEntry>,
+    pub renamed: Vec<RenamedEntry>,
+    pub unchanged: u64,
+    pub from_files: u64,
+    pub to_files: u64,
+    pub from_bytes: u64,
+    pub to_bytes: u64,
+    /// `to_bytes - from_bytes`
+    pub size_delta: i64,
+}
+
+fn delta(old: u64, new: u64) -> i64 {
+    new as i64 - old as i64
+}
+
+/// Compare the state captured by manifest `from_id` with the state captured by `to_id`.
+/// Removed and added files with the same non-empty content are reported as renames.
+pub fn diff_manifests(
+    manifest_manager: &ManifestManager,
+    from_id: &str,
+    to_id: &str,
+) -> Result<ManifestDiff, BackupError> {
+    let from_chain = manifest_manager.load_chain(from_id)?;
+    let to_chain = manifest_manager.load_chain(to_id)?;
+    let (from_set, to_set) = match (from_chain.last(), to_chain.last()) {
+        (Some(from), Some(to)) => (&from.backup_set_id, &to.backup_set_id),
+        _ => return Err(BackupError::Manifest("Manifest chain is empty".to_string())),
+    };
+    if from_set != to_set {
+        return Err(BackupError::Manifest(format!(
+            "Backups {} and {} belong to different backup sets",
+            from_id, to_id
+        )));
+    }
+
+    let from_state = chain_state(&from_chain);
+    let to_state = chain_state(&to_chain);
+
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut modified = Vec::new();
+    let mut unchanged = 0u64;
+
+    for (path, (_, new)) in &to_state {
+        match from_state.get(path) {
+            None => added.push(DiffEntry::from_file(new)),
+            Some((_, old)) if old.hash != new.hash => modified.push(ModifiedEntry {
+                path: path.clone(),
+                old_size: old.size,
+                new_size: new.size,
+                size_delta: delta(old.size, new.size),
+                old_hash: old.hash.clone(),
+                new_hash: new.hash.clone(),
+            }),
+            Some(_) => unchanged += 1,
+        }
+    }
+    for (path, (_, old)) in &from_state {
+        if !to_state.contains_key(path) {
+            removed.push(DiffEntry::from_file(old));
+        }
+    }
+
+    // Pair removed and added files by content; empty files all share a hash, so skip them
+    let mut removed_by_hash: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, entry) in removed.iter().enumerate().rev() {
+        if entry.size > 0 {
+            removed_by_hash.entry(&entry.hash).or_default().push(index);
+        }
+    }
+    let mut renamed = Vec::new();
+    let mut renamed_from = vec![false; removed.len()];
+    added.retain(|entry| {
+        let Some(index) = removed_by_hash
+            .get_mut(entry.hash.as_str())
+            .and_then(|v| v.pop())
+        else {
+            return true;
+        };
+        renamed_from[index] = true;
+        renamed.push(RenamedEntry {
+            from: removed[index].path.clone(),
+            to: entry.path.clone(),
+            size: entry.size,
+            hash: entry.hash.clone(),
+        });
+        false
+    });
+    let mut flags = renamed_from.into_iter();
+    removed.retain(|_| !flags.next().unwrap_or(false));
+
+    let from_bytes = from_state.values().map(|(_, f)| f.size).sum();
+    let to_bytes = to_state.values().map(|(_, f)| f.size).sum();
+
+    Ok(ManifestDiff {
+        from_id: from_id.to_string(),
+        to_id: to_id.to_string(),
+        added,
+        removed,
+        modified,
+        renamed,
+        unchanged,
+        from_files: from_state.len() as u64,
+        to_files: to_state.len() as u64,
+        from_bytes,
+        to_bytes,
+        size_delta: delta(from_bytes, to_bytes),
+    })
+}
@@ -4,18 +4,27 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use thiserror::Error;
 use uuid::Uuid;
 use walkdir::WalkDir;
-use zip::write::SimpleFileOptions;
-use zip::ZipWriter;
+use zip::{ZipArchive, ZipWriter};
 
-use super::manifest::{BackupManifest, FileEntry, ManifestManager};
-use super::set::BackupSet;
+use super::descriptor::{ArchiveDescriptor, DESCRIPTOR_ENTRY};
+use super::estimate::{compression_ratio, plan_space, SizeEstimate};
+use super::manifest::{
+    chain_state, BackupManifest, DeletedFile, FileEntry, ManifestManager, ManifestSummary,
+    SkipStage, SkippedFile, SourceLabel, MANIFEST_SCHEMA_VERSION,
+};
+use super::protection::ProtectionStatus;
+use super::rawpath::{decode_path, entry_indices, entry_names, entry_path};
+use super::set::{BackupMode, BackupSet, FileErrorPolicy};
+use super::spool::{ChunkSink, SpoolWriter, StreamedArchive};
+use super::zipstream::{ArchiveFileWriter, EntryWriter, StreamingZipWriter};
 
 #[derive(Error, Debug)]
 pub enum BackupError {
@@ -29,6 +38,28 @@ pub enum BackupError {
     Cancelled,
     #[error("Manifest error: {0}")]
     Manifest(String),
+    #[error("Catalog error: {0}")]
+    Catalog(#[from] rusqlite::Error),
+    #[error(
+        "Manifest {id} uses format version {found}, but this version of Sentry reads up to version {supported}. Update Sentry to open it"
+    )]
+    UnsupportedManifestVersion {
+        id: String,
+        found: u32,
+        supported: u32,
+    },
+    #[error("Definitions file error: {0}")]
+    Definitions(String),
+    #[error(
+        "Not enough free space on {path}: the backup needs about {} MB but only {} MB is available",
+        required / (1024 * 1024),
+        available / (1024 * 1024)
+    )]
+    InsufficientSpace {
+        path: String,
+        required: u64,
+        available: u64,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,43 +80,196 @@ pub enum BackupStatus {
     Compressing,
     Uploading,
     Completed,
+    /// Finished, but some files were skipped
+    CompletedWithWarnings,
     Failed,
     Cancelled,
 }
 
+impl BackupStatus {
+    fn completion(skipped: &[SkippedFile]) -> Self {
+        if skipped.is_empty() {
+            BackupStatus::Completed
+        } else {
+            BackupStatus::CompletedWithWarnings
+        }
+    }
+
+    fn warning(skipped: &[SkippedFile]) -> Option<String> {
+        (!skipped.is_empty()).then(|| {
+            format!(
+                "{} file(s) could not be read and were skipped",
+                skipped.len()
+            )
+        })
+    }
+}
+
+/// Run a per-file operation under `policy`. The operation returns `Err` for failures that
+/// must abort the run and `Ok(Err(_))` for failures of the file itself; `Ok(None)` means
+/// the file was skipped and recorded in `skipped`.
+fn with_file_policy<T>(
+    policy: FileErrorPolicy,
+    path: &Path,
+    stage: SkipStage,
+    skipped: &mut Vec<SkippedFile>,
+    mut op: impl FnMut() -> Result<Result<T, BackupError>, BackupError>,
+) -> Result<Option<T>, BackupError> {
+    let (attempts, mut backoff_ms) = match policy {
+        FileErrorPolicy::Retry {
+            attempts,
+            backoff_ms,
+        } => (attempts.max(1), backoff_ms),
+        _ => (1, 0),
+    };
+
+    let mut attempt = 1;
+    loop {
+        match op()? {
+            Ok(value) => return Ok(Some(value)),
+            Err(e) if policy == FileErrorPolicy::Fail => return Err(e),
+            Err(_) if attempt < attempts => {
+                std::thread::sleep(std::time::Duration::from_millis(backoff_ms));
+                backoff_ms = backoff_ms.saturating_mul(2);
+                attempt += 1;
+            }
+            Err(e) => {
+                BackupEngine::record_skip(skipped, path, stage, attempt, &e);
+                return Ok(None);
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BackupResult {
     pub id: String,
     pub backup_set_id: String,
     pub started_at: DateTime<Utc>,
     pub completed_at: DateTime<Utc>,
+    pub mode: BackupMode,
     pub total_files: u64,
     pub total_bytes: u64,
     pub compressed_bytes: u64,
     pub files_backed_up: Vec<FileEntry>,
+    /// Files recorded as deleted since the version this run was computed against
+    pub files_deleted: u64,
+    /// Files left out because they could not be read
+    pub skipped_files: Vec<SkippedFile>,
     pub archive_path: PathBuf,
+    /// Set when the archive was streamed to the cloud and never written locally
+    pub streamed: Option<StreamedArchive>,
 }
 
 impl BackupResult {
+    /// Nothing changed since the previous run, so no archive or manifest was written.
+    pub fn is_unchanged(&self) -> bool {
+        self.total_files == 0 && self.total_bytes == 0 && self.files_deleted == 0
+    }
+
     /// Convenience helper for the manifest filename corresponding to this backup result.
     pub fn manifest_file_name(&self) -> String {
         format!("{}.json", self.id)
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreResult {
+    pub manifest_id: String,
+    /// `None` when files went back to their original locations
+    pub target: Option<PathBuf>,
+    pub files_restored: u64,
+    pub bytes_restored: u64,
+}
+
+/// Where a restore writes the files of each source.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RestoreOptions {
+    /// Folder that receives every source under its label; `None` restores each file
+    /// to the path it was backed up from.
+    pub target: Option<PathBuf>,
+    /// Folders that replace `target` for individual sources, keyed by source label.
+    #[serde(default)]
+    pub source_targets: HashMap<String, PathBuf>,
+}
+
+impl RestoreOptions {
+    /// Output path for an archived file, or `None` if it has nowhere to go.
+    fn destination(&self, file: &FileEntry) -> Option<PathBuf> {
+        if let Some(root) = file
+            .source_label
+            .as_ref()
+            .and_then(|label| self.source_targets.get(label))
+        {
+            return Some(root.join(&file.relative_path));
+        }
+        match &self.target {
+            Some(target) => Some(target.join(file.archived_path())),
+            None => Some(file.path.clone()),
+        }
+    }
+}
+
+/// Files that are new or differ from `state`. Files are matched on their source path,
+/// which stays unique when several sources share relative paths.
+fn changed_files(
+    state: &BTreeMap<PathBuf, (usize, &FileEntry)>,
+    current_files: &[FileEntry],
+) -> Vec<FileEntry> {
+    current_files
+        .iter()
+        .filter(|file| {
+            state
+                .get(&file.path)
+                .map(|(_, backed_up)| backed_up.hash != file.hash)
+                .unwrap_or(true)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Files of `state` missing from the current scan. Paths that could not be scanned are
+/// not treated as deleted.
+fn deleted_files(
+    state: &BTreeMap<PathBuf, (usize, &FileEntry)>,
+    current_files: &[FileEntry],
+    skipped: &[SkippedFile],
+) -> Vec<DeletedFile> {
+    let current: HashSet<&Path> = current_files.iter().map(|f| f.path.as_path()).collect();
+    state
+        .keys()
+        .filter(|path| !current.contains(path.as_path()))
+        .filter(|path| {
+            !skipped
+                .iter()
+                .any(|s| s.stage == SkipStage::Scan && path.starts_with(&s.path))
+        })
+        .map(|path| DeletedFile { path: path.clone() })
+        .collect()
+}
+
 pub struct BackupEngine {
-    manifest_manager: ManifestManager,
+    manifest_manager: Arc<ManifestManager>,
     temp_dir: PathBuf,
     chunk_size: usize,
 }
 
 impl BackupEngine {
     pub fn new(data_dir: PathBuf) -> Result<Self, BackupError> {
+        let manifest_manager = Arc::new(ManifestManager::new(data_dir.clone()));
+        Self::with_manifest_manager(data_dir, manifest_manager)
+    }
+
+    /// Engine that shares its manifest manager with other parts of the app.
+    pub fn with_manifest_manager(
+        data_dir: PathBuf,
+        manifest_manager: Arc<ManifestManager>,
+    ) -> Result<Self, BackupError> {
         let temp_dir = data_dir.join("temp");
         fs::create_dir_all(&temp_dir)?;
 
         Ok(Self {
-            manifest_manager: ManifestManager::new(data_dir),
+            manifest_manager,
             temp_dir,
             chunk_size: 10 * 1024 * 1024, // 10MB chunks
         })
@@ -108,19 +292,46 @@ impl BackupEngine {
         Ok(format!("{:x}", hasher.finalize()))
     }
 
-    /// Scan directory and collect file information
+    /// Scan directory and collect file information.
+    /// Unreadable files are handled according to `policy` and recorded in `skipped`.
     pub fn scan_directory(
         &self,
         path: &Path,
         exclude_patterns: &[String],
+        policy: FileErrorPolicy,
+        skipped: &mut Vec<SkippedFile>,
+    ) -> Result<Vec<FileEntry>, BackupError> {
+        Self::scan_directory_with(path, exclude_patterns, policy, skipped, |file_path, _| {
+            Self::calculate_hash(file_path)
+        })
+    }
+
+    /// `scan_directory` with the content hash supplied by `hash`, so callers can reuse
+    /// known hashes for files whose metadata has not changed.
+    fn scan_directory_with(
+        path: &Path,
+        exclude_patterns: &[String],
+        policy: FileErrorPolicy,
+        skipped: &mut Vec<SkippedFile>,
+        hash: impl Fn(&Path, &fs::Metadata) -> Result<String, BackupError>,
     ) -> Result<Vec<FileEntry>, BackupError> {
         let mut entries = Vec::new();
+        let source_label = SourceLabel::for_path(path).label;
 
-        for entry in WalkDir::new(path)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
+        for entry in WalkDir::new(path).follow_links(false) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    // Directories that cannot be listed are not retried; the walk has moved on
+                    let failed_path = e.path().unwrap_or(path).to_path_buf();
+                    let error = std::io::Error::from(e);
+                    if policy == FileErrorPolicy::Fail {
+                        return Err(error.into());
+                    }
+                    Self::record_skip(skipped, &failed_path, SkipStage::Scan, 1, &error);
+                    continue;
+                }
+            };
             let file_path = entry.path();
 
             // Skip directories
@@ -142,8 +353,17 @@ impl BackupEngine {
                 continue;
             }
 
-            let metadata = fs::metadata(file_path)?;
-            let hash = Self::calculate_hash(file_path)?;
+            let scanned = with_file_policy(policy, file_path, SkipStage::Scan, skipped, || {
+                Ok(fs::metadata(file_path)
+                    .map_err(BackupError::from)
+                    .and_then(|metadata| {
+                        let hash = hash(file_path, &metadata)?;
+                        Ok((metadata, hash))
+                    }))
+            })?;
+            let Some((metadata, hash)) = scanned else {
+                continue;
+            };
             let modified = metadata
                 .modified()
                 .map(|t| DateTime::<Utc>::from(t))
@@ -159,66 +379,118 @@ impl BackupEngine {
                 hash,
                 modified,
                 backed_up_at: None,
+                source_label: Some(source_label.clone()),
             });
         }
 
         Ok(entries)
     }
 
-    /// Perform incremental backup - only backup changed files
-    pub fn get_changed_files(
+    fn record_skip(
+        skipped: &mut Vec<SkippedFile>,
+        path: &Path,
+        stage: SkipStage,
+        attempts: u32,
+        error: &dyn std::fmt::Display,
+    ) {
+        eprintln!("Skipped {} during {:?}: {}", path.display(), stage, error);
+        skipped.push(SkippedFile {
+            path: path.to_path_buf(),
+            reason: error.to_string(),
+            stage,
+            attempts,
+        });
+    }
+
+    /// Pick the manifest a run in `mode` is computed against, if there is one
+    pub fn resolve_parent(
         &self,
         backup_set: &BackupSet,
+        mode: BackupMode,
+    ) -> Result<Option<ManifestSummary>, BackupError> {
+        match mode {
+            BackupMode::Full => Ok(None),
+            BackupMode::Incremental => self.manifest_manager.latest_for_set(&backup_set.id),
+            BackupMode::Differential => self.manifest_manager.latest_full_for_set(&backup_set.id),
+        }
+    }
+
+    /// Only keep files that differ from the state captured by `parent` and its ancestors
+    pub fn get_changed_files(
+        &self,
+        parent: &ManifestSummary,
         current_files: &[FileEntry],
     ) -> Result<Vec<FileEntry>, BackupError> {
-        let manifest = self.manifest_manager.load_manifest(&backup_set.id)?;
-
-        let backed_up_hashes: HashMap<PathBuf, String> = manifest
-            .map(|m| {
-                m.files
-                    .iter()
-                    .map(|f| (f.relative_path.clone(), f.hash.clone()))
-                    .collect()
-            })
-            .unwrap_or_default();
-
-        let changed: Vec<FileEntry> = current_files
-            .iter()
-            .filter(|file| {
-                backed_up_hashes
-                    .get(&file.relative_path)
-                    .map(|h| h != &file.hash)
-                    .unwrap_or(true)
-            })
-            .cloned()
-            .collect();
-
-        Ok(changed)
+        let chain = self.manifest_manager.load_chain(&parent.id)?;
+        Ok(changed_files(&chain_state(&chain), current_files))
     }
 
-    /// Create compressed archive from files
+    /// Create compressed archive from the files of `manifest`, named after the manifest.
+    /// Files that cannot be read are handled by the set's `file_error_policy` and added to `skipped`.
     pub fn create_archive(
         &self,
         backup_set: &BackupSet,
-        files: &[FileEntry],
+        manifest: &BackupManifest,
+        skipped: &mut Vec<SkippedFile>,
         progress_callback: impl Fn(BackupProgress),
     ) -> Result<PathBuf, BackupError> {
-        let backup_id = Uuid::new_v4().to_string();
-        let archive_name = format!("{}_{}.zip", backup_set.id, backup_id);
+        let archive_name = format!("{}_{}.zip", backup_set.id, manifest.id);
         let archive_path = self.temp_dir.join(&archive_name);
 
         let file = File::create(&archive_path)?;
-        let mut zip = ZipWriter::new(file);
-        let options = SimpleFileOptions::default()
-            .compression_method(zip::CompressionMethod::Deflated)
-            .compression_level(Some(6));
+        self.write_archive(
+            ArchiveFileWriter::new(file)?,
+            backup_set,
+            manifest,
+            skipped,
+            progress_callback,
+        )?;
+
+        self.place_archive(backup_set, archive_path, &archive_name)
+    }
+
+    /// Compress files straight into `sink` chunk by chunk instead of writing a local archive.
+    /// Only the spool for the chunk being assembled lives in the temp directory.
+    pub fn create_streamed_archive<S: ChunkSink>(
+        &self,
+        backup_set: &BackupSet,
+        manifest: &BackupManifest,
+        sink: S,
+        skipped: &mut Vec<SkippedFile>,
+        progress_callback: impl Fn(BackupProgress),
+    ) -> Result<(S, StreamedArchive), BackupError> {
+        let spool_path = self
+            .temp_dir
+            .join(format!("{}_{}.spool", backup_set.id, Uuid::new_v4()));
+        let spool = SpoolWriter::new(spool_path, self.chunk_size, sink)?;
+
+        let zip = StreamingZipWriter::new(spool);
+        let spool = self.write_archive(zip, backup_set, manifest, skipped, progress_callback)?;
+
+        Ok(spool.finish()?)
+    }
+
+    /// Write the files of `manifest`, then its descriptor so the archive describes itself.
+    fn write_archive<Z: EntryWriter>(
+        &self,
+        mut zip: Z,
+        backup_set: &BackupSet,
+        manifest: &BackupManifest,
+        skipped: &mut Vec<SkippedFile>,
+        progress_callback: impl Fn(BackupProgress),
+    ) -> Result<Z::Output, BackupError> {
+        let files = &manifest.files;
+        let policy = backup_set.file_error_policy;
 
         let total_bytes: u64 = files.iter().map(|f| f.size).sum();
         let total_files = files.len() as u64;
         let mut processed_files = 0u64;
         let mut processed_bytes = 0u64;
+        // Names that are not UTF-8 are escaped, with the original bytes kept in an extra field
+        let archived_paths: Vec<PathBuf> = files.iter().map(|f| f.archived_path()).collect();
+        let names = entry_names(&archived_paths);
 
-        for file_entry in files {
+        for (file_entry, (name, raw_name)) in files.iter().zip(names) {
             progress_callback(BackupProgress {
                 total_files,
                 processed_files,
@@ -229,31 +501,62 @@ impl BackupEngine {
                 error: None,
             });
 
-            let name = file_entry.relative_path.to_string_lossy();
-            zip.start_file(name.as_ref(), options)?;
+            let large = file_entry.size >= u32::MAX as u64;
 
-            let mut source = File::open(&file_entry.path)?;
-            let mut buffer = [0u8; 8192];
+            // Read failures belong to the file; write failures abort the run
+            let archived = with_file_policy(
+                policy,
+                &file_entry.path,
+                SkipStage::Archive,
+                skipped,
+                || {
+                    let mut source = match File::open(&file_entry.path) {
+                        Ok(source) => source,
+                        Err(e) => return Ok(Err(e.into())),
+                    };
+                    zip.start_entry(&name, raw_name.clone(), large)?;
 
-            loop {
-                let bytes_read = source.read(&mut buffer)?;
-                if bytes_read == 0 {
-                    break;
-                }
-                zip.write_all(&buffer[..bytes_read])?;
-                processed_bytes += bytes_read as u64;
-            }
+                    let mut buffer = [0u8; 8192];
+                    let mut copied = 0u64;
+                    loop {
+                        let bytes_read = match source.read(&mut buffer) {
+                            Ok(n) => n,
+                            Err(e) => {
+                                zip.abort_entry()?;
+                                return Ok(Err(e.into()));
+                            }
+                        };
+                        if bytes_read == 0 {
+                            break;
+                        }
+                        zip.write_all(&buffer[..bytes_read])?;
+                        copied += bytes_read as u64;
+                    }
+                    Ok(Ok(copied))
+                },
+            )?;
 
+            processed_bytes += archived.unwrap_or(file_entry.size);
             processed_files += 1;
         }
 
-        zip.finish()?;
+        let mut archived = manifest.clone();
+        archived.record_skips(skipped);
+        ArchiveDescriptor::new(&backup_set.name, &archived).write_to(&mut zip)?;
+
+        zip.finish_archive()
+    }
 
-        // Move archive to local destination if specified
-        if let Some(local_dest) = &backup_set.local_destination {
-            let dest_path = Path::new(local_dest);
-            fs::create_dir_all(dest_path)?;
-            let final_path = dest_path.join(&archive_name);
+    /// Move a finished temp archive to the set's local destination if specified
+    fn place_archive(
+        &self,
+        backup_set: &BackupSet,
+        archive_path: PathBuf,
+        archive_name: &str,
+    ) -> Result<PathBuf, BackupError> {
+        if let Some(dest_path) = backup_set.local_destination.as_deref().map(decode_path) {
+            fs::create_dir_all(&dest_path)?;
+            let final_path = dest_path.join(archive_name);
             fs::rename(&archive_path, &final_path)?;
             Ok(final_path)
         } else {
@@ -261,7 +564,18 @@ impl BackupEngine {
         }
     }
 
-    /// Split archive into chunks for upload
+    pub fn manifest_manager(&self) -> &ManifestManager {
+        &self.manifest_manager
+    }
+
+    /// Set the upload volume size, typically from `AppSettings.chunk_size_mb`
+    pub fn set_chunk_size_mb(&mut self, chunk_size_mb: u32) {
+        self.chunk_size = chunk_size_mb.max(1) as usize * 1024 * 1024;
+    }
+
+    /// Split archive into chunks for upload.
+    /// Volumes are written to the temp directory as `<archive>.partNNN`; an archive that
+    /// fits in one chunk is returned as-is.
     pub fn split_into_chunks(&self, archive_path: &Path) -> Result<Vec<PathBuf>, BackupError> {
         let mut chunks = Vec::new();
         let mut file = File::open(archive_path)?;
@@ -272,16 +586,30 @@ impl BackupEngine {
             return Ok(vec![archive_path.to_path_buf()]);
         }
 
+        let archive_name = archive_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| BackupError::InvalidPath(archive_path.display().to_string()))?;
         let mut chunk_index = 0;
         let mut buffer = vec![0u8; self.chunk_size];
 
         loop {
-            let bytes_read = file.read(&mut buffer)?;
+            // A single read may return less than a full chunk, so fill the buffer explicitly
+            let mut bytes_read = 0;
+            while bytes_read < buffer.len() {
+                let n = file.read(&mut buffer[bytes_read..])?;
+                if n == 0 {
+                    break;
+                }
+                bytes_read += n;
+            }
             if bytes_read == 0 {
                 break;
             }
 
-            let chunk_path = archive_path.with_extension(format!("part{:03}", chunk_index));
+            let chunk_path = self
+                .temp_dir
+                .join(format!("{}.part{:03}", archive_name, chunk_index));
             let mut chunk_file = File::create(&chunk_path)?;
             chunk_file.write_all(&buffer[..bytes_read])?;
 
@@ -292,15 +620,173 @@ impl BackupEngine {
         Ok(chunks)
     }
 
-    /// Execute full backup for a backup set
+    fn plan_space(
+        &self,
+        backup_set: &BackupSet,
+        mode: BackupMode,
+        files: &[FileEntry],
+        streaming: bool,
+    ) -> SizeEstimate {
+        plan_space(
+            backup_set,
+            mode,
+            files,
+            compression_ratio(&self.manifest_manager, &backup_set.id),
+            &self.temp_dir,
+            self.chunk_size as u64,
+            streaming,
+        )
+    }
+
+    /// Estimate the archive a backup would produce right now and whether it fits on disk.
+    /// Scans the sources like a real run but writes nothing.
+    pub fn estimate_backup(
+        &self,
+        backup_set: &BackupSet,
+        mode: BackupMode,
+        streaming: bool,
+    ) -> Result<SizeEstimate, BackupError> {
+        // Estimates never wait on retries; unreadable files simply don't count
+        let mut all_files = Vec::new();
+        let mut skipped = Vec::new();
+        for source_path in backup_set.source_paths() {
+            let files = self.scan_directory(
+                &source_path,
+                &backup_set.exclude_patterns,
+                FileErrorPolicy::Skip,
+                &mut skipped,
+            )?;
+            all_files.extend(files);
+        }
+
+        let parent = self.resolve_parent(backup_set, mode)?;
+        let (mode, files) = match &parent {
+            Some(parent) => (mode, self.get_changed_files(parent, &all_files)?),
+            None => (BackupMode::Full, all_files),
+        };
+
+        Ok(self.plan_space(backup_set, mode, &files, streaming))
+    }
+
+    /// Scan a set's sources and compare them with the cumulative state of its latest backup.
+    /// Files whose size and modification time match the backup reuse its hash instead of
+    /// being read again. Needs only the manifests, so it can run while the engine is busy.
+    pub fn protection_status(
+        manifest_manager: &ManifestManager,
+        backup_set: &BackupSet,
+    ) -> Result<ProtectionStatus, BackupError> {
+        let latest = manifest_manager.latest_for_set(&backup_set.id)?;
+        let chain = match &latest {
+            Some(latest) => manifest_manager.load_chain(&latest.id)?,
+            None => vec![],
+        };
+        let state = chain_state(&chain);
+
+        let mut current = Vec::new();
+        let mut skipped = Vec::new();
+        for source_path in backup_set.source_paths() {
+            if !source_path.exists() {
+                continue;
+            }
+            let files = Self::scan_directory_with(
+                &source_path,
+                &backup_set.exclude_patterns,
+                FileErrorPolicy::Skip,
+                &mut skipped,
+                |file_path, metadata| {
+                    let modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+                    match state.get(file_path) {
+                        Some((_, backed_up))
+                            if backed_up.size == metadata.len()
+                                && Some(backed_up.modified) == modified =>
+                        {
+                            Ok(backed_up.hash.clone())
+                        }
+                        _ => Self::calculate_hash(file_path),
+                    }
+                },
+            )?;
+            current.extend(files);
+        }
+
+        let (new_files, changed): (Vec<FileEntry>, Vec<FileEntry>) =
+            changed_files(&state, &current)
+                .into_iter()
+                .partition(|f| !state.contains_key(&f.path));
+        // A missing source is not a deletion; its files are simply out of reach right now
+        let missing: Vec<PathBuf> = backup_set
+            .source_paths()
+            .into_iter()
+            .filter(|p| !p.exists())
+            .collect();
+        let deleted = deleted_files(&state, &current, &skipped)
+            .into_iter()
+            .filter(|d| !missing.iter().any(|m| d.path.starts_with(m)))
+            .map(|d| {
+                let size = state.get(&d.path).map(|(_, f)| f.size).unwrap_or(0);
+                (d, size)
+            })
+            .collect();
+
+        Ok(ProtectionStatus::new(
+            &backup_set.id,
+            latest.map(|m| (m.id, m.created_at)),
+            &new_files,
+            &changed,
+            deleted,
+            skipped,
+        ))
+    }
+
+    /// Execute a backup for a backup set in the given mode
     pub fn execute_backup(
         &mut self,
         backup_set: &BackupSet,
-        incremental: bool,
+        mode: BackupMode,
+        progress_callback: impl Fn(BackupProgress),
+    ) -> Result<BackupResult, BackupError> {
+        self.run_backup(backup_set, mode, None, progress_callback)
+    }
+
+    /// Execute a backup whose archive is streamed into `sink` instead of kept on disk.
+    /// The resulting manifest has no `archive_path`; the caller records where the sink put it.
+    pub fn execute_streamed_backup(
+        &mut self,
+        backup_set: &BackupSet,
+        mode: BackupMode,
+        sink: &mut dyn ChunkSink,
+        progress_callback: impl Fn(BackupProgress),
+    ) -> Result<BackupResult, BackupError> {
+        self.run_backup(backup_set, mode, Some(sink), progress_callback)
+    }
+
+    fn run_backup(
+        &mut self,
+        backup_set: &BackupSet,
+        mode: BackupMode,
+        sink: Option<&mut dyn ChunkSink>,
         progress_callback: impl Fn(BackupProgress),
     ) -> Result<BackupResult, BackupError> {
         let started_at = Utc::now();
 
+        // A missing source would otherwise scan as empty and pass for an unchanged one
+        if let Some(missing) = backup_set.source_paths().into_iter().find(|p| !p.exists()) {
+            let error = BackupError::InvalidPath(format!(
+                "Source folder {} does not exist",
+                missing.display()
+            ));
+            progress_callback(BackupProgress {
+                total_files: 0,
+                processed_files: 0,
+                total_bytes: 0,
+                processed_bytes: 0,
+                current_file: String::new(),
+                status: BackupStatus::Failed,
+                error: Some(error.to_string()),
+            });
+            return Err(error);
+        }
+
         // Scan all source paths
         progress_callback(BackupProgress {
             total_files: 0,
@@ -313,20 +799,38 @@ impl BackupEngine {
         });
 
         let mut all_files = Vec::new();
-        for source in &backup_set.sources {
-            let source_path = Path::new(source);
-            let files = self.scan_directory(source_path, &backup_set.exclude_patterns)?;
+        let mut skipped_files = Vec::new();
+        for source_path in backup_set.source_paths() {
+            let files = self.scan_directory(
+                &source_path,
+                &backup_set.exclude_patterns,
+                backup_set.file_error_policy,
+                &mut skipped_files,
+            )?;
             all_files.extend(files);
         }
 
-        // Get only changed files if incremental
-        let files_to_backup = if incremental {
-            self.get_changed_files(backup_set, &all_files)?
+        // Without an earlier backup to build on, the run starts a new chain as a full backup
+        let parent = self.resolve_parent(backup_set, mode)?;
+        let mode = if parent.is_some() {
+            mode
         } else {
-            all_files.clone()
+            BackupMode::Full
+        };
+
+        let (files_to_backup, deleted_files) = match &parent {
+            Some(parent) => {
+                let chain = self.manifest_manager.load_chain(&parent.id)?;
+                let state = chain_state(&chain);
+                (
+                    changed_files(&state, &all_files),
+                    deleted_files(&state, &all_files, &skipped_files),
+                )
+            }
+            None => (all_files.clone(), vec![]),
         };
 
-        if files_to_backup.is_empty() {
+        if files_to_backup.is_empty() && deleted_files.is_empty() {
             // Emit a completion event even when there is nothing to back up so the
             // frontend can clear any lingering "Scanning" states.
             progress_callback(BackupProgress {
@@ -335,8 +839,8 @@ impl BackupEngine {
                 total_bytes: 0,
                 processed_bytes: 0,
                 current_file: "No changes detected - already up to date".to_string(),
-                status: BackupStatus::Completed,
-                error: None,
+                status: BackupStatus::completion(&skipped_files),
+                error: BackupStatus::warning(&skipped_files),
             });
 
             return Ok(BackupResult {
@@ -344,56 +848,269 @@ impl BackupEngine {
                 backup_set_id: backup_set.id.clone(),
                 started_at,
                 completed_at: Utc::now(),
+                mode,
                 total_files: 0,
                 total_bytes: 0,
                 compressed_bytes: 0,
                 files_backed_up: vec![],
+                files_deleted: 0,
+                skipped_files,
                 archive_path: PathBuf::new(),
+                streamed: None,
+            });
+        }
+
+        // Refuse up front rather than leave a truncated zip on a full disk
+        let estimate = self.plan_space(backup_set, mode, &files_to_backup, sink.is_some());
+        if let Some(error) = estimate.space_error() {
+            progress_callback(BackupProgress {
+                total_files: files_to_backup.len() as u64,
+                processed_files: 0,
+                total_bytes: estimate.source_bytes,
+                processed_bytes: 0,
+                current_file: String::new(),
+                status: BackupStatus::Failed,
+                error: Some(error.to_string()),
             });
+            return Err(error);
         }
 
+        // The manifest is drafted first so the archive can carry a copy of it
+        let backed_up_at = Utc::now();
+        let mut manifest = BackupManifest {
+            schema_version: MANIFEST_SCHEMA_VERSION,
+            id: Uuid::new_v4().to_string(),
+            backup_set_id: backup_set.id.clone(),
+            created_at: backed_up_at,
+            mode,
+            parent_id: parent.map(|p| p.id),
+            files: files_to_backup
+                .into_iter()
+                .map(|mut file| {
+                    file.backed_up_at = Some(backed_up_at);
+                    file
+                })
+                .collect(),
+            total_size: 0,
+            compressed_size: 0,
+            cloud_location: None,
+            retention_until: backup_set
+                .retention_days
+                .map(|days| backed_up_at + chrono::Duration::days(days as i64)),
+            archive_path: None,
+            merged_from: vec![],
+            superseded_by: None,
+            pinned: false,
+            label: None,
+            skipped_files: vec![],
+            deleted_files: deleted_files.clone(),
+            sources: backup_set
+                .source_paths()
+                .iter()
+                .map(|p| SourceLabel::for_path(p))
+                .collect(),
+        };
+
         // Create archive
-        let archive_path = self.create_archive(backup_set, &files_to_backup, &progress_callback)?;
-        let archive_size = fs::metadata(&archive_path)?.len() as u64;
-        let total_uncompressed_bytes: u64 = files_to_backup.iter().map(|f| f.size).sum();
-
-        // Upload to cloud if enabled
-        if backup_set.cloud_upload {
-            // TODO: Implement cloud upload logic
-            // For now, just create local archive
+        let (archive_path, streamed) = match sink {
+            Some(sink) => {
+                let (_, streamed) = self.create_streamed_archive(
+                    backup_set,
+                    &manifest,
+                    sink,
+                    &mut skipped_files,
+                    &progress_callback,
+                )?;
+                (None, Some(streamed))
+            }
+            None => {
+                let path = self.create_archive(
+                    backup_set,
+                    &manifest,
+                    &mut skipped_files,
+                    &progress_callback,
+                )?;
+                (Some(path), None)
+            }
+        };
+        let archive_size = match (&archive_path, &streamed) {
+            (_, Some(streamed)) => streamed.size,
+            (Some(path), None) => fs::metadata(path)?.len(),
+            (None, None) => 0,
+        };
+
+        // Files that could not be archived are left out so the next run picks them up again
+        manifest.record_skips(&skipped_files);
+        manifest.compressed_size = archive_size;
+        manifest.archive_path = archive_path.clone();
+        let total_uncompressed_bytes = manifest.total_size;
+        let files_backed_up = manifest.files.len() as u64;
+
+        self.manifest_manager.save_manifest(&manifest)?;
+
+        progress_callback(BackupProgress {
+            total_files: files_backed_up,
+            processed_files: files_backed_up,
+            total_bytes: total_uncompressed_bytes,
+            processed_bytes: total_uncompressed_bytes,
+            current_file: "Backup complete".to_string(),
+            status: BackupStatus::completion(&skipped_files),
+            error: BackupStatus::warning(&skipped_files),
+        });
+
+        Ok(BackupResult {
+            id: manifest.id,
+            backup_set_id: backup_set.id.clone(),
+            started_at,
+            completed_at: Utc::now(),
+            mode,
+            total_files: files_backed_up,
+            total_bytes: total_uncompressed_bytes,
+            compressed_bytes: archive_size,
+            files_backed_up: manifest.files,
+            files_deleted: deleted_files.len() as u64,
+            skipped_files,
+            archive_path: archive_path.unwrap_or_default(),
+            streamed,
+        })
+    }
+
+    /// Build a full backup from the set's latest chain using the local archives only.
+    /// Sources are never read. The chain members are listed in `merged_from` but not marked
+    /// as superseded; callers do that once the synthetic full is stored like the set's other
+    /// backups, as retention prunes superseded versions together with their Drive copies.
+    pub fn synthesize_full(
+        &mut self,
+        backup_set: &BackupSet,
+        progress_callback: impl Fn(BackupProgress),
+    ) -> Result<BackupResult, BackupError> {
+        let started_at = Utc::now();
+
+        let latest = self
+            .manifest_manager
+            .latest_for_set(&backup_set.id)?
+            .ok_or_else(|| BackupError::Manifest("No backups recorded for this set".to_string()))?;
+        let chain = self.manifest_manager.load_chain(&latest.id)?;
+
+        if chain.len() < 2 {
+            return Err(BackupError::Manifest(
+                "Latest backup is already a full backup; nothing to merge".to_string(),
+            ));
         }
 
-        // Update manifest
-        let files_with_backup_time: Vec<FileEntry> = files_to_backup
-            .iter()
-            .map(|f| {
-                let mut file = f.clone();
-                file.backed_up_at = Some(Utc::now());
-                file
-            })
+        let mut archives = Vec::with_capacity(chain.len());
+        for manifest in &chain {
+            let path = manifest
+                .archive_path
+                .as_ref()
+                .filter(|p| p.exists())
+                .ok_or_else(|| {
+                    BackupError::InvalidPath(format!(
+                        "Archive for backup {} is not available locally",
+                        manifest.id
+                    ))
+                })?;
+            let mut archive = ZipArchive::new(File::open(path)?)?;
+            let indices = entry_indices(&mut archive)?;
+            archives.push((archive, indices));
+        }
+
+        // Later manifests override earlier ones; remember which archive holds each file
+        let mut files: Vec<(FileEntry, usize)> = chain_state(&chain)
+            .into_values()
+            .map(|(archive_index, file)| (file.clone(), archive_index))
             .collect();
+        files.sort_by_key(|(f, _)| f.archived_path());
+
+        let mut sources: Vec<SourceLabel> = Vec::new();
+        for source in chain.iter().flat_map(|m| &m.sources) {
+            if !sources.contains(source) {
+                sources.push(source.clone());
+            }
+        }
 
-        let manifest = BackupManifest {
+        let total_bytes: u64 = files.iter().map(|(f, _)| f.size).sum();
+        let total_files = files.len() as u64;
+        let merged_from: Vec<String> = chain.iter().map(|m| m.id.clone()).collect();
+
+        let mut manifest = BackupManifest {
+            schema_version: MANIFEST_SCHEMA_VERSION,
             id: Uuid::new_v4().to_string(),
             backup_set_id: backup_set.id.clone(),
             created_at: Utc::now(),
-            files: files_with_backup_time.clone(),
-            total_size: total_uncompressed_bytes,
-            compressed_size: archive_size,
+            mode: BackupMode::Full,
+            parent_id: None,
+            files: files.iter().map(|(f, _)| f.clone()).collect(),
+            total_size: total_bytes,
+            compressed_size: 0,
             cloud_location: None,
             retention_until: backup_set
                 .retention_days
                 .map(|days| Utc::now() + chrono::Duration::days(days as i64)),
+            archive_path: None,
+            merged_from: merged_from.clone(),
+            superseded_by: None,
+            pinned: false,
+            label: None,
+            skipped_files: vec![],
+            sources,
+            deleted_files: vec![],
         };
 
+        let archive_name = format!("{}_{}.zip", backup_set.id, manifest.id);
+        let archive_path = self.temp_dir.join(&archive_name);
+        let mut zip = ZipWriter::new(File::create(&archive_path)?);
+        let mut processed_bytes = 0u64;
+        // Names are assigned afresh, as escaped names from different archives may clash
+        let archived_paths: Vec<PathBuf> = files.iter().map(|(f, _)| f.archived_path()).collect();
+        let names = entry_names(&archived_paths);
+
+        for (processed_files, ((file_entry, archive_index), (name, raw_name))) in
+            files.iter().zip(names).enumerate()
+        {
+            progress_callback(BackupProgress {
+                total_files,
+                processed_files: processed_files as u64,
+                total_bytes,
+                processed_bytes,
+                current_file: file_entry.relative_path.to_string_lossy().to_string(),
+                status: BackupStatus::Compressing,
+                error: None,
+            });
+
+            let (archive, indices) = &mut archives[*archive_index];
+            let index = *indices
+                .get(&file_entry.archived_path())
+                .ok_or(zip::result::ZipError::FileNotFound)?;
+            if raw_name.is_some() {
+                // Raw copies drop extra fields, so entries carrying their raw name are recompressed
+                let large = file_entry.size >= u32::MAX as u64;
+                zip.start_entry(&name, raw_name, large)?;
+                std::io::copy(&mut archive.by_index(index)?, &mut zip)?;
+            } else {
+                // Copy the compressed entry as-is instead of inflating and deflating it again
+                zip.raw_copy_file_rename(archive.by_index_raw(index)?, name)?;
+            }
+            processed_bytes += file_entry.size;
+        }
+
+        ArchiveDescriptor::new(&backup_set.name, &manifest).write_to(&mut zip)?;
+        zip.finish()?;
+        drop(archives);
+
+        let archive_path = self.place_archive(backup_set, archive_path, &archive_name)?;
+        let archive_size = fs::metadata(&archive_path)?.len();
+        manifest.compressed_size = archive_size;
+        manifest.archive_path = Some(archive_path.clone());
+
         self.manifest_manager.save_manifest(&manifest)?;
 
         progress_callback(BackupProgress {
-            total_files: files_to_backup.len() as u64,
-            processed_files: files_to_backup.len() as u64,
-            total_bytes: total_uncompressed_bytes,
-            processed_bytes: total_uncompressed_bytes,
-            current_file: "Backup complete".to_string(),
+            total_files,
+            processed_files: total_files,
+            total_bytes,
+            processed_bytes: total_bytes,
+            current_file: "Synthetic full backup complete".to_string(),
             status: BackupStatus::Completed,
             error: None,
         });
@@ -403,20 +1120,94 @@ impl BackupEngine {
             backup_set_id: backup_set.id.clone(),
             started_at,
             completed_at: Utc::now(),
-            total_files: files_to_backup.len() as u64,
-            total_bytes: total_uncompressed_bytes,
+            mode: BackupMode::Full,
+            total_files,
+            total_bytes,
             compressed_bytes: archive_size,
-            files_backed_up: files_with_backup_time,
+            files_backed_up: manifest.files,
+            files_deleted: 0,
+            skipped_files: vec![],
             archive_path,
+            streamed: None,
         })
     }
 
-    /// Clean up old temp files
+    /// Restore the files of a backup version, applying its chain oldest first.
+    /// Each source goes back to its original folder, under `options.target` in a folder
+    /// named after its label, or to the folder chosen for its label. Entry names are mapped
+    /// back through the manifests, so names that are not UTF-8 come back byte for byte.
+    pub fn restore_backup(
+        &self,
+        manifest_id: &str,
+        options: &RestoreOptions,
+    ) -> Result<RestoreResult, BackupError> {
+        let chain = self.manifest_manager.load_chain(manifest_id)?;
+        restore_chain(&chain, options)
+    }
+
+    /// Restore straight from an archive file using the manifest embedded in it, without the
+    /// manifest store. The archives of the versions it builds on are looked up next to it,
+    /// named `{set_id}_{manifest_id}.zip` as written locally or `backup_{manifest_id}.zip`
+    /// as downloaded from Drive.
+    pub fn restore_archive(
+        archive_path: &Path,
+        options: &RestoreOptions,
+    ) -> Result<RestoreResult, BackupError> {
+        let mut chain = Vec::new();
+        let mut next = Some(archive_path.to_path_buf());
+        while let Some(path) = next.take() {
+            let manifest = ArchiveDescriptor::read(&path)?
+                .ok_or_else(|| {
+                    BackupError::Manifest(format!(
+                        "{} has no embedded manifest; it was written by an older version of Sentry",
+                        path.display()
+                    ))
+                })?
+                .manifest;
+
+            if chain.iter().any(|m: &BackupManifest| m.id == manifest.id) {
+                return Err(BackupError::Manifest(format!(
+                    "Manifest chain loops back to {}",
+                    manifest.id
+                )));
+            }
+            if let Some(parent_id) = &manifest.parent_id {
+                let local_name = format!("{}_{}.zip", manifest.backup_set_id, parent_id);
+                let parent_path = [local_name.clone(), format!("backup_{}.zip", parent_id)]
+                    .iter()
+                    .map(|name| path.with_file_name(name))
+                    .find(|p| p.exists())
+                    .ok_or_else(|| {
+                        BackupError::InvalidPath(format!(
+                            "Backup {} builds on backup {}, but {} is not in the same folder",
+                            manifest.id, parent_id, local_name
+                        ))
+                    })?;
+                next = Some(parent_path);
+            }
+            chain.push(manifest);
+        }
+        chain.reverse();
+        restore_chain(&chain, options)
+    }
+
+    /// Clean up old temp files, keeping archives of pinned versions
     pub fn cleanup_temp(&self) -> Result<(), BackupError> {
         if self.temp_dir.exists() {
+            let mut pinned_archives = Vec::new();
+            for summary in self.manifest_manager.load_index()?.manifests {
+                if summary.pinned {
+                    if let Some(manifest) =
+                        self.manifest_manager.load_manifest_by_id(&summary.id)?
+                    {
+                        pinned_archives.extend(manifest.archive_path);
+                    }
+                }
+            }
+
             for entry in fs::read_dir(&self.temp_dir)? {
                 let entry = entry?;
-                if entry.path().is_file() {
+                if entry.path().is_file() && !pinned_archives.contains(&entry.path()) {
                     fs::remove_file(entry.path())?;
                 }
             }
@@ -424,3 +1215,87 @@ impl BackupEngine {
         Ok(())
     }
 }
+
+/// Restore the files of `chain`, oldest first, keeping for each file the version of the
+/// latest member that holds it.
+fn restore_chain(
+    chain: &[BackupManifest],
+    options: &RestoreOptions,
+) -> Result<RestoreResult, BackupError> {
+    let Some(latest) = chain.last() else {
+        return Err(BackupError::Manifest("No backup to restore".to_string()));
+    };
+    let state = chain_state(chain);
+    let mut files_restored = 0u64;
+    let mut bytes_restored = 0u64;
+
+    for (chain_index, manifest) in chain.iter().enumerate() {
+        let archive_path = manifest
+            .archive_path
+            .as_ref()
+            .filter(|p| p.exists())
+            .ok_or_else(|| {
+                BackupError::InvalidPath(format!(
+                    "Archive for backup {} is not available locally",
+                    manifest.id
+                ))
+            })?;
+        let files: HashMap<PathBuf, &FileEntry> = manifest
+            .files
+            .iter()
+            .map(|f| (f.archived_path(), f))
+            .collect();
+
+        let mut archive = ZipArchive::new(File::open(archive_path)?)?;
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index)?;
+            if entry.is_dir() || entry.name() == DESCRIPTOR_ENTRY {
+                continue;
+            }
+
+            let path = entry_path(entry.name(), entry.extra_data());
+            let (relative_path, output_path) = match files.get(&path) {
+                // Versions replaced later in the chain, and deleted files, are not restored
+                Some(file) if state.get(&file.path).map(|(i, _)| *i) != Some(chain_index) => {
+                    continue
+                }
+                Some(file) => (file.relative_path.clone(), options.destination(file)),
+                None => {
+                    let output_path = options.target.as_ref().map(|t| t.join(&path));
+                    (path, output_path)
+                }
+            };
+            // Never write outside the chosen folder, whatever the archive claims
+            if !relative_path
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)))
+            {
+                return Err(BackupError::InvalidPath(format!(
+                    "Refusing to restore unsafe entry {:?}",
+                    relative_path
+                )));
+            }
+            // Versions recovered from bare archives only know paths relative to their source
+            let output_path = output_path.filter(|p| p.is_absolute()).ok_or_else(|| {
+                BackupError::InvalidPath(format!(
+                    "Entry {:?} has no recorded location; choose a restore folder",
+                    relative_path
+                ))
+            })?;
+
+            if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut output = File::create(&output_path)?;
+            bytes_restored += std::io::copy(&mut entry, &mut output)?;
+            files_restored += 1;
+        }
+    }
+
+    Ok(RestoreResult {
+        manifest_id: latest.id.clone(),
+        target: options.target.clone(),
+        files_restored,
+        bytes_restored,
+    })
+}
@@ -0,0 +1,246 @@
+//! Size Estimation - Predicts archive size and checks free disk space before a backup
+//! Uses compression ratios recorded in earlier manifests to size the output
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::engine::BackupError;
+use super::manifest::{FileEntry, ManifestManager};
+use super::rawpath::decode_path;
+use super::set::{BackupMode, BackupSet};
+
+/// Number of recent manifests averaged into the compression ratio
+const HISTORY_DEPTH: usize = 10;
+/// Local header, central directory record and zip64 extras per entry, excluding the name
+const ENTRY_OVERHEAD: u64 = 128;
+/// Headroom on top of the estimate, in percent
+const SAFETY_MARGIN_PERCENT: u64 = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeRole {
+    Temp,
+    Destination,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeSpace {
+    pub role: VolumeRole,
+    pub path: PathBuf,
+    pub required_bytes: u64,
+    /// `None` when free space could not be determined; such volumes are not checked
+    pub available_bytes: Option<u64>,
+}
+
+impl VolumeSpace {
+    pub fn is_sufficient(&self) -> bool {
+        self.available_bytes
+            .map(|available| available >= self.required_bytes)
+            .unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SizeEstimate {
+    pub backup_set_id: String,
+    pub mode: BackupMode,
+    pub file_count: u64,
+    pub source_bytes: u64,
+    /// Expected compressed/uncompressed ratio
+    pub compression_ratio: f64,
+    /// Whether the ratio comes from earlier manifests rather than the no-compression fallback
+    pub ratio_from_history: bool,
+    pub estimated_archive_bytes: u64,
+    pub volumes: Vec<VolumeSpace>,
+    pub sufficient: bool,
+}
+
+impl SizeEstimate {
+    /// The error a backup should fail with when the estimate does not fit.
+    pub fn space_error(&self) -> Option<BackupError> {
+        self.volumes
+            .iter()
+            .find(|v| !v.is_sufficient())
+            .map(|v| BackupError::InsufficientSpace {
+                path: v.path.display().to_string(),
+                required: v.required_bytes,
+                available: v.available_bytes.unwrap_or(0),
+            })
+    }
+}
+
+/// Average compression ratio of the set's most recent backups, falling back to all sets.
+pub fn compression_ratio(manifest_manager: &ManifestManager, backup_set_id: &str) -> Option<f64> {
+    let mut history = manifest_manager.load_index().ok()?.manifests;
+    history.retain(|m| m.total_size > 0 && m.compressed_size > 0);
+    history.sort_by_key(|m| Reverse(m.created_at));
+
+    let own: Vec<_> = history
+        .iter()
+        .filter(|m| m.backup_set_id == backup_set_id)
+        .take(HISTORY_DEPTH)
+        .collect();
+    let sample = if own.is_empty() {
+        history.iter().take(HISTORY_DEPTH).collect()
+    } else {
+        own
+    };
+    if sample.is_empty() {
+        return None;
+    }
+
+    let total: u64 = sample.iter().map(|m| m.total_size).sum();
+    let compressed: u64 = sample.iter().map(|m| m.compressed_size).sum();
+    Some(compressed as f64 / total as f64)
+}
+
+/// Expected archive size for `files` at `ratio`, including zip structure overhead.
+pub fn estimate_archive_bytes(files: &[FileEntry], ratio: f64) -> u64 {
+    files
+        .iter()
+        .map(|f| {
+            let name_len = f.archived_path().as_os_str().len() as u64;
+            (f.size as f64 * ratio).ceil() as u64 + ENTRY_OVERHEAD + 2 * name_len
+        })
+        .sum()
+}
+
+pub fn with_margin(bytes: u64) -> u64 {
+    bytes + bytes / 100 * SAFETY_MARGIN_PERCENT
+}
+
+/// Size and free-space check for writing `files` of `backup_set`.
+/// `streaming` means the archive goes straight to the cloud and only a spool of
+/// `chunk_size` bytes is kept in temp.
+pub fn plan_space(
+    backup_set: &BackupSet,
+    mode: BackupMode,
+    files: &[FileEntry],
+    ratio: Option<f64>,
+    temp_dir: &Path,
+    chunk_size: u64,
+    streaming: bool,
+) -> SizeEstimate {
+    let compression_ratio = ratio.unwrap_or(1.0);
+    let estimated_archive_bytes = estimate_archive_bytes(files, compression_ratio);
+    let archive = with_margin(estimated_archive_bytes);
+
+    let destination = backup_set.local_destination.as_deref().map(decode_path);
+    let temp_required = if streaming {
+        // The spool only ever holds the chunk being assembled
+        with_margin(chunk_size)
+    } else if backup_set.cloud_upload && destination.is_none() {
+        // The archive and its upload volumes exist side by side until the upload finishes
+        archive * 2
+    } else {
+        archive
+    };
+
+    let mut volumes = vec![VolumeSpace {
+        role: VolumeRole::Temp,
+        path: temp_dir.to_path_buf(),
+        required_bytes: temp_required,
+        available_bytes: available_space(temp_dir).ok(),
+    }];
+
+    if let Some(destination) = destination {
+        // Archives are moved from temp by rename, so a shared volume only needs room once
+        if same_volume(temp_dir, &destination) {
+            volumes[0].role = VolumeRole::Destination;
+            volumes[0].path = destination;
+        } else {
+            let available_bytes = available_space(&destination).ok();
+            volumes.push(VolumeSpace {
+                role: VolumeRole::Destination,
+                path: destination,
+                required_bytes: archive,
+                available_bytes,
+            });
+        }
+    }
+
+    SizeEstimate {
+        backup_set_id: backup_set.id.clone(),
+        mode,
+        file_count: files.len() as u64,
+        source_bytes: files.iter().map(|f| f.size).sum(),
+        compression_ratio,
+        ratio_from_history: ratio.is_some(),
+        estimated_archive_bytes,
+        sufficient: volumes.iter().all(VolumeSpace::is_sufficient),
+        volumes,
+    }
+}
+
+/// The closest ancestor that exists, so destinations can be checked before they are created.
+fn existing_ancestor(path: &Path) -> io::Result<&Path> {
+    path.ancestors()
+        .find(|p| p.exists())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.display().to_string()))
+}
+
+#[cfg(unix)]
+fn same_volume(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    let dev = |p: &Path| {
+        existing_ancestor(p)
+            .and_then(std::fs::metadata)
+            .map(|m| m.dev())
+    };
+    matches!((dev(a), dev(b)), (Ok(x), Ok(y)) if x == y)
+}
+
+#[cfg(windows)]
+fn same_volume(a: &Path, b: &Path) -> bool {
+    use std::path::Component;
+
+    let prefix = |p: &Path| match p.components().next() {
+        Some(Component::Prefix(prefix)) => Some(prefix.as_os_str().to_ascii_uppercase()),
+        _ => None,
+    };
+    matches!((prefix(a), prefix(b)), (Some(x), Some(y)) if x == y)
+}
+
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = existing_ancestor(path)?;
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    // SAFETY: c_path is a valid NUL-terminated string and stat is a valid out pointer
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(windows)]
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let path = existing_ancestor(path)?;
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available: u64 = 0;
+
+    // SAFETY: wide is NUL-terminated and the out pointer is valid; unused outputs may be null
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(available)
+}
@@ -0,0 +1,322 @@
+//! Run History - Persistent record of every backup run, successful or not
+//! One JSON file per run so concurrent runs never rewrite each other's records; file names
+//! start with the run's start time and carry its set, so listings sort and filter on names alone
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use super::engine::{BackupError, BackupResult};
+use super::set::BackupMode;
+use crate::persist::write_json_atomic;
+
+/// Oldest records beyond this many are deleted when a run is saved
+const MAX_RUNS: usize = 500;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Completed,
+    CompletedWithWarnings,
+    /// Nothing changed since the previous run, so no archive was written
+    NoChanges,
+    Failed,
+    /// The app exited before the run finished
+    Interrupted,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunUploadStatus {
+    /// The set does not upload to the cloud
+    NotRequested,
+    Uploaded,
+    /// Upload was wanted but could not start, e.g. Drive is not connected
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunUpload {
+    pub status: RunUploadStatus,
+    /// Number of archive volumes stored in the cloud
+    pub volumes: u32,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupRun {
+    pub id: String,
+    pub backup_set_id: String,
+    pub backup_set_name: Option<String>,
+    /// What started the run, e.g. "manual" or "schedule"
+    pub trigger: String,
+    /// Requested mode until the run finishes, then the mode the engine actually used
+    pub mode: BackupMode,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: RunStatus,
+    pub files_backed_up: u64,
+    pub files_skipped: u64,
+    #[serde(default)]
+    pub files_deleted: u64,
+    pub total_bytes: u64,
+    pub compressed_bytes: u64,
+    pub upload: RunUpload,
+    pub errors: Vec<String>,
+    pub manifest_id: Option<String>,
+}
+
+impl BackupRun {
+    pub fn start(backup_set_id: &str, trigger: &str, mode: BackupMode) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            backup_set_id: backup_set_id.to_string(),
+            backup_set_name: None,
+            trigger: trigger.to_string(),
+            mode,
+            started_at: Utc::now(),
+            finished_at: None,
+            status: RunStatus::Running,
+            files_backed_up: 0,
+            files_skipped: 0,
+            files_deleted: 0,
+            total_bytes: 0,
+            compressed_bytes: 0,
+            upload: RunUpload {
+                status: RunUploadStatus::NotRequested,
+                volumes: 0,
+                message: None,
+            },
+            errors: vec![],
+            manifest_id: None,
+        }
+    }
+
+    /// Copy the engine's outcome into the record.
+    pub fn record_result(&mut self, result: &BackupResult) {
+        self.mode = result.mode;
+        self.files_backed_up = result.total_files;
+        self.files_skipped = result.skipped_files.len() as u64;
+        self.files_deleted = result.files_deleted;
+        self.total_bytes = result.total_bytes;
+        self.compressed_bytes = result.compressed_bytes;
+        if result.is_unchanged() {
+            self.status = RunStatus::NoChanges;
+        } else {
+            self.manifest_id = Some(result.id.clone());
+        }
+    }
+
+    pub fn record_upload(
+        &mut self,
+        status: RunUploadStatus,
+        volumes: u32,
+        message: Option<String>,
+    ) {
+        if let Some(message) = &message {
+            if status != RunUploadStatus::Uploaded {
+                self.errors.push(message.clone());
+            }
+        }
+        self.upload = RunUpload {
+            status,
+            volumes,
+            message,
+        };
+    }
+
+    /// Close the record. Failed runs keep `error`; others are rated by what was recorded.
+    pub fn finish(&mut self, error: Option<String>) {
+        self.finished_at = Some(Utc::now());
+        if let Some(error) = error {
+            self.errors.push(error);
+            self.status = RunStatus::Failed;
+        } else if self.status == RunStatus::Running {
+            self.status = if self.files_skipped > 0 || !self.errors.is_empty() {
+                RunStatus::CompletedWithWarnings
+            } else {
+                RunStatus::Completed
+            };
+        }
+    }
+}
+
+/// One page of runs, newest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupRunPage {
+    pub runs: Vec<BackupRun>,
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+pub struct RunHistory {
+    data_dir: PathBuf,
+}
+
+/// A run record located by its file name, before it is read.
+struct RunFile {
+    path: PathBuf,
+    backup_set_id: String,
+    id: String,
+}
+
+impl RunHistory {
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self { data_dir }
+    }
+
+    fn runs_dir(&self) -> PathBuf {
+        self.data_dir.join("runs")
+    }
+
+    /// `{started_at}_{backup_set_id}_{id}.json`; the timestamp sorts as text.
+    fn run_path(&self, run: &BackupRun) -> PathBuf {
+        self.runs_dir().join(format!(
+            "{}_{}_{}.json",
+            run.started_at.format("%Y%m%dT%H%M%S%6f"),
+            run.backup_set_id,
+            run.id
+        ))
+    }
+
+    /// Write a run, replacing any earlier copy of the same record.
+    pub fn save_run(&self, run: &BackupRun) -> Result<(), BackupError> {
+        fs::create_dir_all(self.runs_dir())?;
+        write_json_atomic(&self.run_path(run), run)?;
+
+        if run.finished_at.is_some() {
+            self.prune()?;
+        }
+        Ok(())
+    }
+
+    pub fn load_run(&self, id: &str) -> Result<Option<BackupRun>, BackupError> {
+        match self.run_files()?.into_iter().find(|f| f.id == id) {
+            Some(file) => Ok(Some(Self::read_run(&file.path)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn read_run(path: &Path) -> Result<BackupRun, BackupError> {
+        let reader = BufReader::new(File::open(path)?);
+        serde_json::from_reader(reader).map_err(|e| BackupError::Manifest(e.to_string()))
+    }
+
+    /// Every run record, newest first, from the file names alone.
+    fn run_files(&self) -> Result<Vec<RunFile>, BackupError> {
+        let dir = self.runs_dir();
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        self.migrate_legacy(&dir)?;
+
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if name.ends_with(".json") {
+                names.push(name);
+            }
+        }
+        names.sort_by(|a, b| b.cmp(a));
+
+        Ok(names
+            .into_iter()
+            .filter_map(|name| {
+                let stem = name.strip_suffix(".json")?;
+                // Run ids are UUIDs, so the set id is whatever lies between the first and last `_`
+                let (_, rest) = stem.split_once('_')?;
+                let (backup_set_id, id) = rest.rsplit_once('_')?;
+                Some(RunFile {
+                    path: dir.join(&name),
+                    backup_set_id: backup_set_id.to_string(),
+                    id: id.to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// Rename records saved as `{id}.json` by earlier versions. Unreadable ones are reported
+    /// and left alone.
+    fn migrate_legacy(&self, dir: &Path) -> Result<(), BackupError> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let is_legacy = path.extension().and_then(|e| e.to_str()) == Some("json")
+                && path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .is_some_and(|s| !s.contains('_'));
+            if !is_legacy {
+                continue;
+            }
+            match Self::read_run(&path) {
+                Ok(run) => fs::rename(&path, self.run_path(&run))?,
+                Err(e) => eprintln!("Failed to read run record {}: {}", path.display(), e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Mark runs still recorded as running as interrupted. Call at startup, before any run
+    /// can start, so only records left behind by a crash or forced exit are affected.
+    pub fn mark_interrupted(&self) -> Result<usize, BackupError> {
+        let mut interrupted = 0;
+        for file in self.run_files()? {
+            let mut run = match Self::read_run(&file.path) {
+                Ok(run) => run,
+                Err(e) => {
+                    eprintln!("Failed to read run record {}: {}", file.path.display(), e);
+                    continue;
+                }
+            };
+            if run.status == RunStatus::Running {
+                run.status = RunStatus::Interrupted;
+                run.errors
+                    .push("The app exited before the run finished".to_string());
+                write_json_atomic(&file.path, &run)?;
+                interrupted += 1;
+            }
+        }
+        Ok(interrupted)
+    }
+
+    /// A page of runs, optionally for one set only. Only the records on the page are read.
+    pub fn list_runs(
+        &self,
+        backup_set_id: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<BackupRunPage, BackupError> {
+        let mut files = self.run_files()?;
+        if let Some(backup_set_id) = backup_set_id {
+            files.retain(|f| f.backup_set_id == backup_set_id);
+        }
+
+        let mut runs = Vec::new();
+        for file in files.iter().skip(offset).take(limit) {
+            match Self::read_run(&file.path) {
+                Ok(run) => runs.push(run),
+                Err(e) => eprintln!("Failed to read run record {}: {}", file.path.display(), e),
+            }
+        }
+
+        Ok(BackupRunPage {
+            total: files.len(),
+            runs,
+            offset,
+            limit,
+        })
+    }
+
+    fn prune(&self) -> Result<(), BackupError> {
+        for file in self.run_files()?.into_iter().skip(MAX_RUNS) {
+            fs::remove_file(&file.path)?;
+        }
+        Ok(())
+    }
+}
@@ -3,20 +3,84 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::fs::{self, File};
-use std::io::{BufReader, BufWriter};
-use std::path::PathBuf;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
+use super::catalog::{Catalog, CatalogEntry, CatalogVersion};
 use super::engine::BackupError;
+use super::rawpath::{path_bytes, serde_path};
+use super::search::FileSearchQuery;
+use super::set::BackupMode;
+use crate::persist::write_json_atomic;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileEntry {
+    #[serde(with = "serde_path")]
     pub path: PathBuf,
+    #[serde(with = "serde_path")]
     pub relative_path: PathBuf,
     pub size: u64,
     pub hash: String,
     pub modified: DateTime<Utc>,
     pub backed_up_at: Option<DateTime<Utc>>,
+    /// Label of the source folder the file was found in; `None` in manifests written
+    /// before sources were namespaced.
+    #[serde(default)]
+    pub source_label: Option<String>,
+}
+
+impl FileEntry {
+    /// Path of the file inside the archive: `relative_path` under its source label.
+    pub fn archived_path(&self) -> PathBuf {
+        match &self.source_label {
+            Some(label) => Path::new(label).join(&self.relative_path),
+            None => self.relative_path.clone(),
+        }
+    }
+}
+
+/// A source folder of a backup and the label its files are stored under in the archive.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SourceLabel {
+    pub label: String,
+    #[serde(with = "serde_path")]
+    pub path: PathBuf,
+}
+
+impl SourceLabel {
+    /// Stable label for a source: its folder name plus a short hash of the full path,
+    /// so two sources with the same name never share a namespace.
+    pub fn for_path(path: &Path) -> Self {
+        let name: String = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let name = if name.trim_matches('.').is_empty() {
+            "source".to_string()
+        } else {
+            name
+        };
+        let digest = format!("{:x}", Sha256::digest(path_bytes(path)));
+
+        Self {
+            label: format!("{}-{}", name, &digest[..8]),
+            path: path.to_path_buf(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,16 +99,181 @@ pub struct CloudChunk {
     pub hash: String,
 }
 
+impl CloudLocation {
+    /// Whether the Drive file `file_id` is one of this location's volumes.
+    pub fn contains_file(&self, file_id: &str) -> bool {
+        self.file_id == file_id || self.chunks.iter().any(|c| c.file_id == file_id)
+    }
+}
+
+/// Manifests written before modes existed were standalone archives.
+fn legacy_mode() -> BackupMode {
+    BackupMode::Full
+}
+
+/// Format version written by this build. Raise it together with a new step in
+/// `MIGRATIONS` whenever the structure of `BackupManifest` changes.
+pub const MANIFEST_SCHEMA_VERSION: u32 = 2;
+
+/// Upgrade steps; entry `n` takes a manifest from version `n + 1` to `n + 2`.
+/// Manifests without a version field are version 1.
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[migrate_v1_to_v2];
+
+/// Version 1 manifests predate backup modes; each was a standalone archive.
+fn migrate_v1_to_v2(manifest: &mut Map<String, Value>) {
+    manifest
+        .entry("mode")
+        .or_insert_with(|| Value::from("full"));
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BackupManifest {
+    /// Format version; always `MANIFEST_SCHEMA_VERSION` once read, as older manifests are
+    /// upgraded on load.
+    pub schema_version: u32,
     pub id: String,
     pub backup_set_id: String,
     pub created_at: DateTime<Utc>,
+    pub mode: BackupMode,
+    /// Manifest this one was computed against; `None` for full backups.
+    #[serde(default)]
+    pub parent_id: Option<String>,
     pub files: Vec<FileEntry>,
     pub total_size: u64,
     pub compressed_size: u64,
     pub cloud_location: Option<CloudLocation>,
     pub retention_until: Option<DateTime<Utc>>,
+    /// Where the archive was written locally, if it was kept.
+    #[serde(default)]
+    pub archive_path: Option<PathBuf>,
+    /// Chain members folded into this manifest by a synthetic full backup.
+    #[serde(default)]
+    pub merged_from: Vec<String>,
+    /// Synthetic full backup that replaced this manifest; set means it may be pruned.
+    #[serde(default)]
+    pub superseded_by: Option<String>,
+    /// Pinned versions are on hold and never deleted by retention or cleanup.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Free-form note, e.g. why the version is pinned.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Files that could not be read and are missing from this version.
+    #[serde(default)]
+    pub skipped_files: Vec<SkippedFile>,
+    /// Source folders of the set and their labels; empty for manifests written before
+    /// sources were namespaced.
+    #[serde(default)]
+    pub sources: Vec<SourceLabel>,
+    /// Files of the parent state that were gone from the sources when this version was made.
+    #[serde(default)]
+    pub deleted_files: Vec<DeletedFile>,
+}
+
+impl BackupManifest {
+    /// Parse a manifest of any supported version, upgrading it to the current format.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, BackupError> {
+        let value =
+            serde_json::from_slice(bytes).map_err(|e| BackupError::Manifest(e.to_string()))?;
+        Self::from_value(value)
+    }
+
+    /// Like `from_slice`, reading from `reader`.
+    pub fn from_reader(reader: impl Read) -> Result<Self, BackupError> {
+        let value =
+            serde_json::from_reader(reader).map_err(|e| BackupError::Manifest(e.to_string()))?;
+        Self::from_value(value)
+    }
+
+    /// Like `from_slice`, for a manifest already parsed as JSON.
+    pub fn from_value(mut value: Value) -> Result<Self, BackupError> {
+        let Value::Object(manifest) = &mut value else {
+            return Err(BackupError::Manifest(
+                "Manifest is not a JSON object".to_string(),
+            ));
+        };
+
+        let version = match manifest.get("schema_version") {
+            None => 1,
+            Some(version) => version
+                .as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(|| {
+                    BackupError::Manifest(format!("Invalid manifest schema version {}", version))
+                })?,
+        };
+        if version > MANIFEST_SCHEMA_VERSION {
+            return Err(BackupError::UnsupportedManifestVersion {
+                id: manifest
+                    .get("id")
+                    .and_then(Value::as_str)
+                    .unwrap_or("unknown")
+                    .to_string(),
+                found: version,
+                supported: MANIFEST_SCHEMA_VERSION,
+            });
+        }
+
+        for step in MIGRATIONS.iter().skip(version.saturating_sub(1) as usize) {
+            step(manifest);
+        }
+        manifest.insert(
+            "schema_version".to_string(),
+            Value::from(MANIFEST_SCHEMA_VERSION),
+        );
+
+        serde_json::from_value(value).map_err(|e| BackupError::Manifest(e.to_string()))
+    }
+
+    /// Drop the files that could not be archived and record every file skipped by the run.
+    pub fn record_skips(&mut self, skipped: &[SkippedFile]) {
+        self.files.retain(|f| {
+            !skipped
+                .iter()
+                .any(|s| s.stage == SkipStage::Archive && s.path == f.path)
+        });
+        self.total_size = self.files.iter().map(|f| f.size).sum();
+        self.skipped_files = skipped.to_vec();
+    }
+}
+
+/// A file that existed in the version a backup was computed against but not in the backup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletedFile {
+    #[serde(with = "serde_path")]
+    pub path: PathBuf,
+}
+
+/// Files present as of the last manifest of `chain` (oldest first), keyed by source path,
+/// with the index of the chain member holding each file's current version.
+pub fn chain_state(chain: &[BackupManifest]) -> BTreeMap<PathBuf, (usize, &FileEntry)> {
+    let mut state = BTreeMap::new();
+    for (index, manifest) in chain.iter().enumerate() {
+        for deleted in &manifest.deleted_files {
+            state.remove(&deleted.path);
+        }
+        for file in &manifest.files {
+            state.insert(file.path.clone(), (index, file));
+        }
+    }
+    state
+}
+
+/// A file left out of a backup because it could not be read.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedFile {
+    #[serde(with = "serde_path")]
+    pub path: PathBuf,
+    pub reason: String,
+    pub stage: SkipStage,
+    pub attempts: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SkipStage {
+    Scan,
+    Archive,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,19 +287,103 @@ pub struct ManifestSummary {
     pub id: String,
     pub backup_set_id: String,
     pub created_at: DateTime<Utc>,
+    #[serde(default = "legacy_mode")]
+    pub mode: BackupMode,
+    #[serde(default)]
+    pub parent_id: Option<String>,
     pub file_count: u64,
     pub total_size: u64,
     pub compressed_size: u64,
     pub is_uploaded: bool,
+    #[serde(default)]
+    pub superseded_by: Option<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// Everything known about a version except its file list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionInfo {
+    pub summary: ManifestSummary,
+    pub retention_until: Option<DateTime<Utc>>,
+    pub archive_path: Option<PathBuf>,
+    /// Whether the archive is still on local disk
+    pub archive_available: bool,
+    pub cloud_location: Option<CloudLocation>,
+    pub sources: Vec<SourceLabel>,
+    pub skipped_files: Vec<SkippedFile>,
+    pub merged_from: Vec<String>,
+    /// Versions a restore of this one also needs, oldest (the full backup) first
+    pub chain: Vec<ManifestSummary>,
+    /// Ids of the versions built directly on this one
+    pub dependents: Vec<String>,
+}
+
+/// One page of a manifest's files, in manifest order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestFilePage {
+    pub files: Vec<FileEntry>,
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+/// A version and one page of its files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestDetail {
+    pub version: VersionInfo,
+    pub files: ManifestFilePage,
 }
 
+impl ManifestSummary {
+    pub fn from_manifest(manifest: &BackupManifest) -> Self {
+        Self {
+            id: manifest.id.clone(),
+            backup_set_id: manifest.backup_set_id.clone(),
+            created_at: manifest.created_at,
+            mode: manifest.mode,
+            parent_id: manifest.parent_id.clone(),
+            file_count: manifest.files.len() as u64,
+            total_size: manifest.total_size,
+            compressed_size: manifest.compressed_size,
+            is_uploaded: manifest.cloud_location.is_some(),
+            superseded_by: manifest.superseded_by.clone(),
+            pinned: manifest.pinned,
+            label: manifest.label.clone(),
+        }
+    }
+}
+
+/// A manifest file that could not be read while rebuilding the catalog.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnreadableManifest {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Outcome of regenerating the catalog from the manifest files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexRebuild {
+    pub manifests: usize,
+    pub unreadable: Vec<UnreadableManifest>,
+}
+
+/// Manifests are stored as JSON documents, one per version, and indexed in a SQLite
+/// catalog that answers listings and lookups.
 pub struct ManifestManager {
     data_dir: PathBuf,
+    /// Opened on first use
+    catalog: Mutex<Option<Catalog>>,
 }
 
 impl ManifestManager {
     pub fn new(data_dir: PathBuf) -> Self {
-        Self { data_dir }
+        Self {
+            data_dir,
+            catalog: Mutex::new(None),
+        }
     }
 
     fn manifests_dir(&self) -> PathBuf {
@@ -81,169 +394,431 @@ impl ManifestManager {
         self.manifests_dir().join(format!("{}.json", id))
     }
 
-    fn index_path(&self) -> PathBuf {
+    /// JSON index used before the catalog; renamed once its manifests are imported
+    fn legacy_index_path(&self) -> PathBuf {
         self.manifests_dir().join("index.json")
     }
 
+    fn catalog_path(&self) -> PathBuf {
+        self.manifests_dir().join("catalog.db")
+    }
+
+    /// Manifests read from Drive, kept apart from the local index so retention never sees them
+    fn cloud_cache_dir(&self) -> PathBuf {
+        self.manifests_dir().join("cloud")
+    }
+
+    /// Run `f` against the catalog, opening it first if needed.
+    fn with_catalog<T>(
+        &self,
+        f: impl FnOnce(&mut Catalog) -> Result<T, BackupError>,
+    ) -> Result<T, BackupError> {
+        let mut guard = self
+            .catalog
+            .lock()
+            .map_err(|_| BackupError::Manifest("Manifest catalog lock poisoned".to_string()))?;
+        let catalog = match &mut *guard {
+            Some(catalog) => catalog,
+            slot => slot.insert(self.open_catalog()?),
+        };
+        f(catalog)
+    }
+
+    /// Open the catalog and, the first time, import the manifests written before it existed.
+    /// A catalog database that cannot be opened is set aside and rebuilt from the manifests.
+    fn open_catalog(&self) -> Result<Catalog, BackupError> {
+        fs::create_dir_all(self.manifests_dir())?;
+        let path = self.catalog_path();
+        let mut catalog = match Catalog::open(&path) {
+            Ok(catalog) => catalog,
+            Err(e) => {
+                eprintln!("Failed to open manifest catalog, rebuilding it: {}", e);
+                let aside = path
+                    .with_extension(format!("db.corrupt-{}", Utc::now().format("%Y%m%d%H%M%S")));
+                fs::rename(&path, &aside)?;
+                for suffix in ["-wal", "-shm"] {
+                    let _ = fs::remove_file(format!("{}{}", path.display(), suffix));
+                }
+                Catalog::open(&path)?
+            }
+        };
+        if catalog.json_imported()? {
+            return Ok(catalog);
+        }
+
+        let rebuild = Self::rebuild_from_files(&mut catalog, &self.read_manifest_files()?)?;
+        if rebuild.manifests > 0 {
+            println!("Imported {} manifests into the catalog", rebuild.manifests);
+        }
+        let legacy_index = self.legacy_index_path();
+        if legacy_index.exists() {
+            fs::rename(&legacy_index, legacy_index.with_extension("json.migrated"))?;
+        }
+        Ok(catalog)
+    }
+
+    /// Every manifest file on disk, parsed. Unreadable files are returned as errors.
+    fn read_manifest_files(
+        &self,
+    ) -> Result<Vec<Result<BackupManifest, UnreadableManifest>>, BackupError> {
+        let mut manifests = Vec::new();
+        for entry in fs::read_dir(self.manifests_dir())? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json")
+                || path == self.legacy_index_path()
+            {
+                continue;
+            }
+            let parsed = File::open(&path)
+                .map_err(BackupError::from)
+                .and_then(|f| BackupManifest::from_reader(BufReader::new(f)));
+            manifests.push(parsed.map_err(|e| {
+                eprintln!("Failed to read manifest {}: {}", path.display(), e);
+                UnreadableManifest {
+                    path,
+                    error: e.to_string(),
+                }
+            }));
+        }
+        Ok(manifests)
+    }
+
+    fn rebuild_from_files(
+        catalog: &mut Catalog,
+        files: &[Result<BackupManifest, UnreadableManifest>],
+    ) -> Result<IndexRebuild, BackupError> {
+        let manifests: Vec<BackupManifest> = files
+            .iter()
+            .filter_map(|f| f.as_ref().ok().cloned())
+            .collect();
+        catalog.rebuild(&manifests)?;
+        Ok(IndexRebuild {
+            manifests: manifests.len(),
+            unreadable: files
+                .iter()
+                .filter_map(|f| f.as_ref().err().cloned())
+                .collect(),
+        })
+    }
+
+    /// Regenerate the catalog from the individual manifest files, e.g. after it was lost
+    /// or manifests were copied in by hand. The files are read under the catalog lock, so a
+    /// manifest saved meanwhile is either read here or indexed by its own save afterwards.
+    pub fn rebuild_index(&self) -> Result<IndexRebuild, BackupError> {
+        fs::create_dir_all(self.manifests_dir())?;
+        self.with_catalog(|catalog| {
+            let files = self.read_manifest_files()?;
+            Self::rebuild_from_files(catalog, &files)
+        })
+    }
+
     pub fn save_manifest(&self, manifest: &BackupManifest) -> Result<(), BackupError> {
         let dir = self.manifests_dir();
         fs::create_dir_all(&dir)?;
 
-        let path = self.manifest_path(&manifest.id);
-        let file = File::create(&path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, manifest)
-            .map_err(|e| BackupError::Manifest(e.to_string()))?;
-
-        // Update index
-        self.update_index(manifest)?;
+        write_json_atomic(&self.manifest_path(&manifest.id), manifest)?;
 
-        Ok(())
+        self.with_catalog(|catalog| catalog.upsert(manifest))
     }
 
-    pub fn load_manifest(&self, backup_set_id: &str) -> Result<Option<BackupManifest>, BackupError> {
-        let index = self.load_index()?;
-        
+    pub fn load_manifest(
+        &self,
+        backup_set_id: &str,
+    ) -> Result<Option<BackupManifest>, BackupError> {
         // Find the latest manifest for this backup set
-        let latest = index
-            .manifests
-            .iter()
-            .filter(|m| m.backup_set_id == backup_set_id)
-            .max_by_key(|m| m.created_at);
-
-        match latest {
-            Some(summary) => {
-                let path = self.manifest_path(&summary.id);
-                if path.exists() {
-                    let file = File::open(&path)?;
-                    let reader = BufReader::new(file);
-                    let manifest: BackupManifest = serde_json::from_reader(reader)
-                        .map_err(|e| BackupError::Manifest(e.to_string()))?;
-                    Ok(Some(manifest))
-                } else {
-                    Ok(None)
-                }
-            }
+        match self.latest_for_set(backup_set_id)? {
+            Some(summary) => self.load_manifest_by_id(&summary.id),
             None => Ok(None),
         }
     }
 
+    /// Latest full backup recorded for a set, the base of any differential run.
+    pub fn latest_full_for_set(
+        &self,
+        backup_set_id: &str,
+    ) -> Result<Option<ManifestSummary>, BackupError> {
+        self.with_catalog(|catalog| catalog.latest(backup_set_id, Some(BackupMode::Full)))
+    }
+
+    /// Latest manifest of any mode recorded for a set.
+    pub fn latest_for_set(
+        &self,
+        backup_set_id: &str,
+    ) -> Result<Option<ManifestSummary>, BackupError> {
+        self.with_catalog(|catalog| catalog.latest(backup_set_id, None))
+    }
+
+    /// Load a manifest together with its ancestors, oldest (the full backup) first.
+    pub fn load_chain(&self, id: &str) -> Result<Vec<BackupManifest>, BackupError> {
+        let mut chain = Vec::new();
+        let mut next = Some(id.to_string());
+
+        while let Some(current) = next {
+            if chain.iter().any(|m: &BackupManifest| m.id == current) {
+                return Err(BackupError::Manifest(format!(
+                    "Manifest chain loops back to {}",
+                    current
+                )));
+            }
+
+            let manifest = self.load_manifest_by_id(&current)?.ok_or_else(|| {
+                BackupError::Manifest(format!("Manifest {} missing from chain", current))
+            })?;
+            next = manifest.parent_id.clone();
+            chain.push(manifest);
+        }
+
+        chain.reverse();
+        Ok(chain)
+    }
+
     pub fn load_manifest_by_id(&self, id: &str) -> Result<Option<BackupManifest>, BackupError> {
         let path = self.manifest_path(id);
         if path.exists() {
             let file = File::open(&path)?;
             let reader = BufReader::new(file);
-            let manifest: BackupManifest = serde_json::from_reader(reader)
-                .map_err(|e| BackupError::Manifest(e.to_string()))?;
-            Ok(Some(manifest))
+            Ok(Some(BackupManifest::from_reader(reader)?))
         } else {
             Ok(None)
         }
     }
 
+    /// Summaries of every local manifest, oldest first.
     pub fn load_index(&self) -> Result<ManifestIndex, BackupError> {
-        let path = self.index_path();
-        if path.exists() {
-            let file = File::open(&path)?;
-            let reader = BufReader::new(file);
-            let index: ManifestIndex = serde_json::from_reader(reader)
-                .map_err(|e| BackupError::Manifest(e.to_string()))?;
-            Ok(index)
-        } else {
+        self.with_catalog(|catalog| {
             Ok(ManifestIndex {
-                manifests: vec![],
-                last_updated: Utc::now(),
+                manifests: catalog.summaries()?,
+                last_updated: catalog.last_updated()?.unwrap_or_else(Utc::now),
             })
-        }
+        })
     }
 
-    fn update_index(&self, manifest: &BackupManifest) -> Result<(), BackupError> {
-        let mut index = self.load_index()?;
+    pub fn list_manifests_for_set(
+        &self,
+        backup_set_id: &str,
+    ) -> Result<Vec<ManifestSummary>, BackupError> {
+        self.with_catalog(|catalog| catalog.summaries_for_set(backup_set_id))
+    }
 
-        let summary = ManifestSummary {
-            id: manifest.id.clone(),
-            backup_set_id: manifest.backup_set_id.clone(),
-            created_at: manifest.created_at,
-            file_count: manifest.files.len() as u64,
-            total_size: manifest.total_size,
-            compressed_size: manifest.compressed_size,
-            is_uploaded: manifest.cloud_location.is_some(),
-        };
+    /// Metadata of a version: its chain, dependents, locations and skipped files.
+    pub fn version_info(&self, id: &str) -> Result<VersionInfo, BackupError> {
+        self.with_catalog(|catalog| Self::describe(catalog, id))
+    }
 
-        // Remove old entry if exists
-        index.manifests.retain(|m| m.id != manifest.id);
-        index.manifests.push(summary);
-        index.last_updated = Utc::now();
+    fn describe(catalog: &Catalog, id: &str) -> Result<VersionInfo, BackupError> {
+        let entry: CatalogEntry = catalog
+            .entry(id)?
+            .ok_or_else(|| BackupError::Manifest(format!("Manifest {} not found", id)))?;
+
+        let mut chain = Vec::new();
+        let mut next = entry.summary.parent_id.clone();
+        while let Some(current) = next {
+            if current == id || chain.iter().any(|m: &ManifestSummary| m.id == current) {
+                return Err(BackupError::Manifest(format!(
+                    "Manifest chain loops back to {}",
+                    current
+                )));
+            }
+            let summary = catalog.summary(&current)?.ok_or_else(|| {
+                BackupError::Manifest(format!("Manifest {} missing from chain", current))
+            })?;
+            next = summary.parent_id.clone();
+            chain.push(summary);
+        }
+        chain.reverse();
+
+        Ok(VersionInfo {
+            dependents: catalog.dependents(id)?,
+            archive_available: entry.archive_path.as_ref().is_some_and(|p| p.exists()),
+            summary: entry.summary,
+            retention_until: entry.retention_until,
+            archive_path: entry.archive_path,
+            cloud_location: entry.cloud_location,
+            sources: entry.sources,
+            skipped_files: entry.skipped_files,
+            merged_from: entry.merged_from,
+            chain,
+        })
+    }
 
-        let path = self.index_path();
-        let file = File::create(&path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, &index)
-            .map_err(|e| BackupError::Manifest(e.to_string()))?;
+    /// A version's metadata together with files `offset..offset + limit`.
+    pub fn manifest_detail(
+        &self,
+        id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<ManifestDetail, BackupError> {
+        self.with_catalog(|catalog| {
+            let version = Self::describe(catalog, id)?;
+            Ok(ManifestDetail {
+                files: ManifestFilePage {
+                    files: catalog.files(id, offset, limit)?,
+                    total: version.summary.file_count as usize,
+                    offset,
+                    limit,
+                },
+                version,
+            })
+        })
+    }
+
+    pub fn delete_manifest(&self, id: &str) -> Result<(), BackupError> {
+        self.with_catalog(|catalog| {
+            if catalog.summary(id)?.is_some_and(|m| m.pinned) {
+                return Err(BackupError::Manifest(format!(
+                    "Backup {} is pinned and cannot be deleted",
+                    id
+                )));
+            }
+            let path = self.manifest_path(id);
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+            catalog.remove(id)
+        })
+    }
 
+    pub fn update_cloud_location(
+        &self,
+        manifest_id: &str,
+        cloud_location: CloudLocation,
+    ) -> Result<(), BackupError> {
+        if let Some(mut manifest) = self.load_manifest_by_id(manifest_id)? {
+            manifest.cloud_location = Some(cloud_location);
+            self.save_manifest(&manifest)?;
+        }
         Ok(())
     }
 
-    pub fn list_manifests_for_set(&self, backup_set_id: &str) -> Result<Vec<ManifestSummary>, BackupError> {
-        let index = self.load_index()?;
-        Ok(index
-            .manifests
-            .into_iter()
-            .filter(|m| m.backup_set_id == backup_set_id)
-            .collect())
+    /// Record where the archive of a version can be read on this machine.
+    pub fn set_archive_path(
+        &self,
+        manifest_id: &str,
+        archive_path: &Path,
+    ) -> Result<(), BackupError> {
+        if let Some(mut manifest) = self.load_manifest_by_id(manifest_id)? {
+            manifest.archive_path = Some(archive_path.to_path_buf());
+            self.save_manifest(&manifest)?;
+        }
+        Ok(())
     }
 
-    pub fn delete_manifest(&self, id: &str) -> Result<(), BackupError> {
-        let path = self.manifest_path(id);
+    /// Store a manifest found outside the local store, e.g. in Drive, unless a copy is
+    /// already here. An archive path that does not exist on this machine is dropped.
+    /// Returns whether the manifest was stored; stored manifests are indexed right away.
+    pub fn import_manifest(&self, manifest: &BackupManifest) -> Result<bool, BackupError> {
+        let path = self.manifest_path(&manifest.id);
         if path.exists() {
-            fs::remove_file(&path)?;
+            return Ok(false);
         }
 
-        // Update index
-        let mut index = self.load_index()?;
-        index.manifests.retain(|m| m.id != id);
-        index.last_updated = Utc::now();
-
-        let index_path = self.index_path();
-        let file = File::create(&index_path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, &index)
-            .map_err(|e| BackupError::Manifest(e.to_string()))?;
+        let mut manifest = manifest.clone();
+        if !manifest.archive_path.as_ref().is_some_and(|p| p.exists()) {
+            manifest.archive_path = None;
+        }
+        fs::create_dir_all(self.manifests_dir())?;
+        write_json_atomic(&path, &manifest)?;
+        self.with_catalog(|catalog| catalog.upsert(&manifest))?;
+        Ok(true)
+    }
 
-        Ok(())
+    /// Pin or unpin a version; a provided label replaces the existing one.
+    pub fn set_pinned(
+        &self,
+        id: &str,
+        pinned: bool,
+        label: Option<String>,
+    ) -> Result<BackupManifest, BackupError> {
+        let mut manifest = self
+            .load_manifest_by_id(id)?
+            .ok_or_else(|| BackupError::Manifest(format!("Manifest {} not found", id)))?;
+
+        manifest.pinned = pinned;
+        if label.is_some() {
+            manifest.label = label;
+        }
+        self.save_manifest(&manifest)?;
+        Ok(manifest)
     }
 
-    pub fn cleanup_expired(&self) -> Result<Vec<String>, BackupError> {
+    /// The pinned version whose cloud copy includes the Drive file `file_id`, if any. The
+    /// manifest's Drive id is not recorded, so it is matched by `file_name` instead.
+    pub fn pinned_cloud_owner(
+        &self,
+        file_id: &str,
+        file_name: Option<&str>,
+    ) -> Result<Option<String>, BackupError> {
         let index = self.load_index()?;
-        let now = Utc::now();
-        let mut deleted = Vec::new();
-
-        for summary in &index.manifests {
-            if let Some(manifest) = self.load_manifest_by_id(&summary.id)? {
-                if let Some(retention_until) = manifest.retention_until {
-                    if retention_until < now {
-                        self.delete_manifest(&summary.id)?;
-                        deleted.push(summary.id.clone());
-                    }
-                }
+        for summary in index.manifests.iter().filter(|m| m.pinned && m.is_uploaded) {
+            if file_name == Some(format!("manifest_{}.json", summary.id).as_str()) {
+                return Ok(Some(summary.id.clone()));
+            }
+            let Some(manifest) = self.load_manifest_by_id(&summary.id)? else {
+                continue;
+            };
+            if manifest
+                .cloud_location
+                .as_ref()
+                .is_some_and(|l| l.contains_file(file_id))
+            {
+                return Ok(Some(manifest.id));
             }
         }
+        Ok(None)
+    }
 
-        Ok(deleted)
+    /// Flag manifests as replaced by the synthetic full backup `superseded_by`.
+    pub fn mark_superseded(&self, ids: &[String], superseded_by: &str) -> Result<(), BackupError> {
+        for id in ids {
+            if let Some(mut manifest) = self.load_manifest_by_id(id)? {
+                manifest.superseded_by = Some(superseded_by.to_string());
+                self.save_manifest(&manifest)?;
+            }
+        }
+        Ok(())
     }
 
-    pub fn update_cloud_location(
+    /// Stored versions of local manifests matching the catalog-side criteria of `query`.
+    pub fn find_versions(
         &self,
-        manifest_id: &str,
-        cloud_location: CloudLocation,
-    ) -> Result<(), BackupError> {
-        if let Some(mut manifest) = self.load_manifest_by_id(manifest_id)? {
-            manifest.cloud_location = Some(cloud_location);
-            self.save_manifest(&manifest)?;
-        }
+        query: &FileSearchQuery,
+    ) -> Result<Vec<CatalogVersion>, BackupError> {
+        self.with_catalog(|catalog| catalog.find_versions(query))
+    }
+
+    /// Keep a copy of a manifest downloaded from Drive for offline search.
+    pub fn cache_cloud_manifest(&self, manifest: &BackupManifest) -> Result<(), BackupError> {
+        let dir = self.cloud_cache_dir();
+        fs::create_dir_all(&dir)?;
+
+        write_json_atomic(&dir.join(format!("{}.json", manifest.id)), manifest)?;
         Ok(())
     }
 
+    /// Manifests cached from Drive. Unreadable cache entries are reported and skipped.
+    pub fn load_cached_cloud_manifests(&self) -> Result<Vec<BackupManifest>, BackupError> {
+        let dir = self.cloud_cache_dir();
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut manifests = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let parsed = File::open(&path)
+                .map_err(BackupError::from)
+                .and_then(|f| BackupManifest::from_reader(BufReader::new(f)));
+            match parsed {
+                Ok(manifest) => manifests.push(manifest),
+                Err(e) => eprintln!("Failed to read cached manifest {}: {}", path.display(), e),
+            }
+        }
+        Ok(manifests)
+    }
+
     pub fn get_all_cloud_manifests(&self) -> Result<Vec<BackupManifest>, BackupError> {
         let index = self.load_index()?;
         let mut manifests = Vec::new();
@@ -1,9 +1,37 @@
+pub mod catalog;
+pub mod descriptor;
+pub mod diff;
 pub mod engine;
+pub mod estimate;
+pub mod history;
 pub mod manifest;
+pub mod portable;
+pub mod protection;
+pub mod rawpath;
+pub mod recovery;
+pub mod retention;
 pub mod scheduler;
+pub mod search;
 pub mod set;
+pub mod spool;
+pub mod validate;
+pub mod zipstream;
 
+pub use catalog::*;
+pub use descriptor::*;
+pub use diff::*;
 pub use engine::*;
+pub use estimate::*;
+pub use history::*;
 pub use manifest::*;
+pub use portable::*;
+pub use protection::*;
+pub use rawpath::*;
+pub use recovery::*;
+pub use retention::*;
 pub use scheduler::*;
+pub use search::*;
 pub use set::*;
+pub use spool::*;
+pub use validate::*;
+pub use zipstream::*;
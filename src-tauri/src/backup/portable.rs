@@ -0,0 +1,510 @@
+//! Portable Definitions - Export and import of backup sets and schedules
+//! Paths under well-known folders are written as placeholders so one file fits every machine
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use super::engine::BackupError;
+use super::rawpath::{decode_path, encode_path};
+use super::scheduler::{Schedule, ScheduleType, WeatherTrigger};
+use super::set::{BackupMode, BackupSet, BackupSetManager, FileErrorPolicy, GfsPolicy};
+use super::validate::validate_backup_set;
+use crate::persist::write_atomic;
+
+/// Version of the definitions file written by this build
+pub const DEFINITIONS_FORMAT_VERSION: u32 = 1;
+
+/// A backup set without its id, history or machine-specific paths.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortableBackupSet {
+    /// Identifies the set within the file; schedules refer to it
+    pub key: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Source folders, possibly starting with a placeholder such as `{documents}`
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub compression_level: u8,
+    #[serde(default)]
+    pub mode: BackupMode,
+    #[serde(default)]
+    pub retention_days: Option<u32>,
+    #[serde(default)]
+    pub max_versions: Option<u32>,
+    #[serde(default)]
+    pub gfs_policy: Option<GfsPolicy>,
+    #[serde(default)]
+    pub file_error_policy: FileErrorPolicy,
+    #[serde(default)]
+    pub cloud_upload: bool,
+    #[serde(default)]
+    pub local_destination: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortableSchedule {
+    pub name: String,
+    /// `key` of the set the schedule runs
+    pub backup_set: String,
+    pub schedule_type: ScheduleType,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub time: Option<String>,
+    #[serde(default)]
+    pub days_of_week: Vec<u8>,
+    #[serde(default)]
+    pub day_of_month: Option<u32>,
+    #[serde(default)]
+    pub weather_trigger_enabled: bool,
+    #[serde(default)]
+    pub weather_alert_types: Vec<String>,
+    #[serde(default)]
+    pub weather_triggers: Vec<WeatherTrigger>,
+    #[serde(default)]
+    pub mode: Option<BackupMode>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefinitionsFile {
+    pub format_version: u32,
+    pub exported_at: DateTime<Utc>,
+    #[serde(default)]
+    pub backup_sets: Vec<PortableBackupSet>,
+    #[serde(default)]
+    pub schedules: Vec<PortableSchedule>,
+}
+
+/// What to do when an imported item has the same name as an existing one.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Keep the existing item; schedules of a skipped set attach to the existing set
+    #[default]
+    Skip,
+    /// Import under a new name such as "Documents (2)"
+    Rename,
+    /// Overwrite the existing definition, keeping its id and backup history
+    Replace,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DefinitionKind {
+    BackupSet,
+    Schedule,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportAction {
+    Created,
+    Renamed,
+    Replaced,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportOutcome {
+    pub kind: DefinitionKind,
+    /// Name in the file
+    pub name: String,
+    pub action: ImportAction,
+    /// Id of the created or replaced item
+    pub id: Option<String>,
+    /// New name, reason for skipping, or validation warnings
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub outcomes: Vec<ImportOutcome>,
+}
+
+/// Well-known folders that may stand in for the start of a path, most specific first.
+fn known_folders() -> Vec<(&'static str, PathBuf)> {
+    [
+        ("documents", dirs::document_dir()),
+        ("desktop", dirs::desktop_dir()),
+        ("downloads", dirs::download_dir()),
+        ("pictures", dirs::picture_dir()),
+        ("music", dirs::audio_dir()),
+        ("videos", dirs::video_dir()),
+        ("home", dirs::home_dir()),
+    ]
+    .into_iter()
+    .filter_map(|(name, dir)| dir.map(|d| (name, d)))
+    .collect()
+}
+
+/// Replace a leading well-known folder with its placeholder, using `/` for the rest.
+fn to_portable(encoded: &str, folders: &[(&'static str, PathBuf)]) -> String {
+    let path = decode_path(encoded);
+    for (name, dir) in folders {
+        if let Ok(rest) = path.strip_prefix(dir) {
+            let mut portable = format!("{{{}}}", name);
+            for component in rest.components() {
+                let Component::Normal(part) = component else {
+                    return encoded.to_string();
+                };
+                let Some(part) = part.to_str() else {
+                    return encoded.to_string();
+                };
+                portable.push('/');
+                portable.push_str(part);
+            }
+            return portable;
+        }
+    }
+    encoded.to_string()
+}
+
+/// Resolve a leading placeholder for this machine. Paths without one are returned as they are.
+fn from_portable(portable: &str, folders: &[(&'static str, PathBuf)]) -> Result<String, String> {
+    let Some(rest) = portable.strip_prefix('{') else {
+        return Ok(portable.to_string());
+    };
+    let Some((name, rest)) = rest.split_once('}') else {
+        return Ok(portable.to_string());
+    };
+    let Some((_, dir)) = folders.iter().find(|(known, _)| *known == name) else {
+        return Err(format!(
+            "The {{{}}} folder is not known on this computer",
+            name
+        ));
+    };
+
+    let path = rest
+        .split(['/', '\\'])
+        .filter(|part| !part.is_empty())
+        .fold(dir.clone(), |path, part| path.join(part));
+    Ok(encode_path(&path))
+}
+
+impl DefinitionsFile {
+    /// Portable form of `sets` and the schedules that run them.
+    pub fn export(sets: &[&BackupSet], schedules: &[Schedule]) -> Self {
+        let folders = known_folders();
+        let backup_sets = sets
+            .iter()
+            .map(|set| PortableBackupSet {
+                key: set.id.clone(),
+                name: set.name.clone(),
+                description: set.description.clone(),
+                sources: set
+                    .sources
+                    .iter()
+                    .map(|s| to_portable(s, &folders))
+                    .collect(),
+                exclude_patterns: set.exclude_patterns.clone(),
+                enabled: set.enabled,
+                compression_level: set.compression_level,
+                mode: set.mode,
+                retention_days: set.retention_days,
+                max_versions: set.max_versions,
+                gfs_policy: set.gfs_policy.clone(),
+                file_error_policy: set.file_error_policy,
+                cloud_upload: set.cloud_upload,
+                local_destination: set
+                    .local_destination
+                    .as_deref()
+                    .map(|d| to_portable(d, &folders)),
+            })
+            .collect();
+        let schedules = schedules
+            .iter()
+            .filter(|s| sets.iter().any(|set| set.id == s.backup_set_id))
+            .map(|s| PortableSchedule {
+                name: s.name.clone(),
+                backup_set: s.backup_set_id.clone(),
+                schedule_type: s.schedule_type.clone(),
+                enabled: s.enabled,
+                time: s.time.clone(),
+                days_of_week: s.days_of_week.clone(),
+                day_of_month: s.day_of_month,
+                weather_trigger_enabled: s.weather_trigger_enabled,
+                weather_alert_types: s.weather_alert_types.clone(),
+                weather_triggers: s.weather_triggers.clone(),
+                mode: s.mode,
+            })
+            .collect();
+
+        Self {
+            format_version: DEFINITIONS_FORMAT_VERSION,
+            exported_at: Utc::now(),
+            backup_sets,
+            schedules,
+        }
+    }
+
+    /// Write the file as TOML when `path` ends in `.toml`, JSON otherwise.
+    pub fn write(&self, path: &Path) -> Result<(), BackupError> {
+        let contents = if is_toml(path) {
+            toml::to_string_pretty(self).map_err(|e| BackupError::Definitions(e.to_string()))?
+        } else {
+            serde_json::to_string_pretty(self)
+                .map_err(|e| BackupError::Definitions(e.to_string()))?
+        };
+        write_atomic(path, |writer| {
+            std::io::Write::write_all(writer, contents.as_bytes())
+        })?;
+        Ok(())
+    }
+
+    /// Read a file written by `write`, on this or an older version.
+    pub fn read(path: &Path) -> Result<Self, BackupError> {
+        let contents = fs::read_to_string(path)?;
+        let file: Self = if is_toml(path) {
+            toml::from_str(&contents).map_err(|e| BackupError::Definitions(e.to_string()))?
+        } else {
+            serde_json::from_str(&contents).map_err(|e| BackupError::Definitions(e.to_string()))?
+        };
+        if file.format_version > DEFINITIONS_FORMAT_VERSION {
+            return Err(BackupError::Definitions(format!(
+                "The file uses format version {}, but this version of Sentry reads up to version {}",
+                file.format_version, DEFINITIONS_FORMAT_VERSION
+            )));
+        }
+        Ok(file)
+    }
+
+    /// Merge the definitions into `sets` and `schedules`. Imported items get new ids, and
+    /// schedules are pointed at the ids their sets received.
+    pub fn import(
+        &self,
+        sets: &mut BackupSetManager,
+        schedules: &mut Vec<Schedule>,
+        policy: ConflictPolicy,
+    ) -> ImportReport {
+        let folders = known_folders();
+        let mut outcomes = Vec::new();
+        // File key -> id of the set the key's schedules should run
+        let mut set_ids: HashMap<&str, String> = HashMap::new();
+
+        for portable in &self.backup_sets {
+            let outcome = import_set(portable, &folders, sets, policy);
+            if let Some(id) = &outcome.id {
+                set_ids.insert(&portable.key, id.clone());
+            }
+            outcomes.push(outcome);
+        }
+
+        for portable in &self.schedules {
+            let Some(set_id) = set_ids.get(portable.backup_set.as_str()) else {
+                outcomes.push(ImportOutcome {
+                    kind: DefinitionKind::Schedule,
+                    name: portable.name.clone(),
+                    action: ImportAction::Skipped,
+                    id: None,
+                    message: Some("Its backup set was not imported".to_string()),
+                });
+                continue;
+            };
+            outcomes.push(import_schedule(portable, set_id, schedules, policy));
+        }
+
+        ImportReport { outcomes }
+    }
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("toml"))
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+/// `name` with the lowest " (n)" suffix not yet taken.
+fn unique_name(name: &str, taken: impl Fn(&str) -> bool) -> String {
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| !taken(candidate))
+        .unwrap_or_else(|| name.to_string())
+}
+
+fn import_set(
+    portable: &PortableBackupSet,
+    folders: &[(&'static str, PathBuf)],
+    sets: &mut BackupSetManager,
+    policy: ConflictPolicy,
+) -> ImportOutcome {
+    let mut outcome = ImportOutcome {
+        kind: DefinitionKind::BackupSet,
+        name: portable.name.clone(),
+        action: ImportAction::Skipped,
+        id: None,
+        message: None,
+    };
+
+    let resolved = portable
+        .sources
+        .iter()
+        .map(|s| from_portable(s, folders))
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|sources| {
+            let destination = portable
+                .local_destination
+                .as_deref()
+                .map(|d| from_portable(d, folders))
+                .transpose()?;
+            Ok((sources, destination))
+        });
+    let (sources, local_destination) = match resolved {
+        Ok(resolved) => resolved,
+        Err(message) => {
+            outcome.message = Some(message);
+            return outcome;
+        }
+    };
+
+    let existing = sets
+        .sets
+        .iter()
+        .find(|s| same_name(&s.name, &portable.name))
+        .map(|s| s.id.clone());
+    let mut set = match (&existing, policy) {
+        (Some(id), ConflictPolicy::Skip) => {
+            outcome.id = Some(id.clone());
+            outcome.message = Some("A backup set with this name already exists".to_string());
+            return outcome;
+        }
+        (Some(id), ConflictPolicy::Replace) => sets
+            .get_set(id)
+            .cloned()
+            .unwrap_or_else(|| BackupSet::new(portable.name.clone())),
+        (Some(_), ConflictPolicy::Rename) => {
+            let name = unique_name(&portable.name, |candidate| {
+                sets.sets.iter().any(|s| same_name(&s.name, candidate))
+            });
+            BackupSet::new(name)
+        }
+        (None, _) => BackupSet::new(portable.name.clone()),
+    };
+
+    set.description = portable.description.clone();
+    set.sources = sources.clone();
+    set.paths = sources;
+    set.exclude_patterns = portable.exclude_patterns.clone();
+    set.enabled = portable.enabled;
+    set.compression_level = portable.compression_level;
+    set.mode = portable.mode;
+    set.retention_days = portable.retention_days;
+    set.max_versions = portable.max_versions;
+    set.gfs_policy = portable.gfs_policy.clone();
+    set.file_error_policy = portable.file_error_policy;
+    set.cloud_upload = portable.cloud_upload;
+    set.local_destination = local_destination;
+    set.updated_at = Utc::now();
+
+    let report = validate_backup_set(&set);
+    if let Some(message) = report.error_message() {
+        outcome.message = Some(message);
+        return outcome;
+    }
+    let warnings: Vec<&str> = report.warnings.iter().map(|w| w.message.as_str()).collect();
+
+    outcome.id = Some(set.id.clone());
+    match existing {
+        Some(_) if policy == ConflictPolicy::Replace => {
+            outcome.action = ImportAction::Replaced;
+            sets.update_set(set);
+        }
+        Some(_) => {
+            outcome.action = ImportAction::Renamed;
+            outcome.message = Some(format!("Imported as {}", set.name));
+            sets.add_set(set);
+        }
+        None => {
+            outcome.action = ImportAction::Created;
+            sets.add_set(set);
+        }
+    }
+    if !warnings.is_empty() {
+        let warnings = warnings.join("; ");
+        outcome.message = Some(match outcome.message.take() {
+            Some(message) => format!("{}. {}", message, warnings),
+            None => warnings,
+        });
+    }
+    outcome
+}
+
+fn import_schedule(
+    portable: &PortableSchedule,
+    set_id: &str,
+    schedules: &mut Vec<Schedule>,
+    policy: ConflictPolicy,
+) -> ImportOutcome {
+    let mut outcome = ImportOutcome {
+        kind: DefinitionKind::Schedule,
+        name: portable.name.clone(),
+        action: ImportAction::Created,
+        id: None,
+        message: None,
+    };
+
+    let existing = schedules
+        .iter()
+        .position(|s| s.backup_set_id == set_id && same_name(&s.name, &portable.name));
+    let mut schedule = match (existing, policy) {
+        (Some(index), ConflictPolicy::Skip) => {
+            outcome.action = ImportAction::Skipped;
+            outcome.id = Some(schedules[index].id.clone());
+            outcome.message =
+                Some("A schedule with this name already runs this backup set".to_string());
+            return outcome;
+        }
+        (Some(index), ConflictPolicy::Replace) => {
+            outcome.action = ImportAction::Replaced;
+            schedules[index].clone()
+        }
+        (Some(_), ConflictPolicy::Rename) => {
+            let name = unique_name(&portable.name, |candidate| {
+                schedules
+                    .iter()
+                    .any(|s| s.backup_set_id == set_id && same_name(&s.name, candidate))
+            });
+            outcome.action = ImportAction::Renamed;
+            outcome.message = Some(format!("Imported as {}", name));
+            Schedule::new(name, set_id.to_string(), portable.schedule_type.clone())
+        }
+        (None, _) => Schedule::new(
+            portable.name.clone(),
+            set_id.to_string(),
+            portable.schedule_type.clone(),
+        ),
+    };
+
+    schedule.schedule_type = portable.schedule_type.clone();
+    schedule.enabled = portable.enabled;
+    schedule.time = portable.time.clone();
+    schedule.days_of_week = portable.days_of_week.clone();
+    schedule.day_of_month = portable.day_of_month;
+    schedule.weather_trigger_enabled = portable.weather_trigger_enabled;
+    schedule.weather_alert_types = portable.weather_alert_types.clone();
+    schedule.weather_triggers = portable.weather_triggers.clone();
+    schedule.mode = portable.mode;
+    schedule.updated_at = Utc::now();
+    schedule.calculate_next_run();
+
+    outcome.id = Some(schedule.id.clone());
+    match existing {
+        Some(index) if policy == ConflictPolicy::Replace => schedules[index] = schedule,
+        _ => schedules.push(schedule),
+    }
+    outcome
+}
@@ -0,0 +1,91 @@
+//! Protection Status - What a set's sources hold right now that no backup has captured
+//! Compares a fresh scan with the cumulative state of the set's latest backup
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use super::manifest::{DeletedFile, FileEntry, SkippedFile};
+use super::rawpath::serde_path;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingChange {
+    #[serde(with = "serde_path")]
+    pub path: PathBuf,
+    pub size: u64,
+    /// Last modification on disk; `None` for deleted files
+    pub modified: Option<DateTime<Utc>>,
+}
+
+impl PendingChange {
+    fn from_file(file: &FileEntry) -> Self {
+        Self {
+            path: file.path.clone(),
+            size: file.size,
+            modified: Some(file.modified),
+        }
+    }
+}
+
+/// Changes on disk since the latest backup of a set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtectionStatus {
+    pub backup_set_id: String,
+    pub checked_at: DateTime<Utc>,
+    /// `None` when the set has never been backed up, so every file is new
+    pub last_backup_id: Option<String>,
+    pub last_backup_at: Option<DateTime<Utc>>,
+    pub new_files: Vec<PendingChange>,
+    pub changed_files: Vec<PendingChange>,
+    pub deleted_files: Vec<PendingChange>,
+    /// Size of the new and changed files, which no backup holds yet
+    pub bytes_at_risk: u64,
+    /// Modification time of the oldest new or changed file
+    pub oldest_unprotected_change: Option<DateTime<Utc>>,
+    /// Files that could not be read during the check
+    pub skipped_files: Vec<SkippedFile>,
+}
+
+impl ProtectionStatus {
+    /// Build the status from the engine's change detection. `deleted` carries the sizes
+    /// last backed up.
+    pub fn new(
+        backup_set_id: &str,
+        last_backup: Option<(String, DateTime<Utc>)>,
+        new_files: &[FileEntry],
+        changed_files: &[FileEntry],
+        deleted: Vec<(DeletedFile, u64)>,
+        skipped_files: Vec<SkippedFile>,
+    ) -> Self {
+        let unprotected = || new_files.iter().chain(changed_files);
+        let (last_backup_id, last_backup_at) = last_backup.unzip();
+
+        Self {
+            backup_set_id: backup_set_id.to_string(),
+            checked_at: Utc::now(),
+            last_backup_id,
+            last_backup_at,
+            bytes_at_risk: unprotected().map(|f| f.size).sum(),
+            oldest_unprotected_change: unprotected().map(|f| f.modified).min(),
+            new_files: new_files.iter().map(PendingChange::from_file).collect(),
+            changed_files: changed_files.iter().map(PendingChange::from_file).collect(),
+            deleted_files: deleted
+                .into_iter()
+                .map(|(file, size)| PendingChange {
+                    path: file.path,
+                    size,
+                    modified: None,
+                })
+                .collect(),
+            skipped_files,
+        }
+    }
+
+    /// Nothing on disk differs from the latest backup.
+    pub fn is_protected(&self) -> bool {
+        self.last_backup_id.is_some()
+            && self.new_files.is_empty()
+            && self.changed_files.is_empty()
+            && self.deleted_files.is_empty()
+    }
+}
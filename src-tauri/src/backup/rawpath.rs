@@ -0,0 +1,189 @@
+//! Raw Paths - Lossless handling of file names that are not valid UTF-8
+//! Keeps the original bytes in manifests, zip entries and set sources so files restore under their real names
+
+use serde::{de::Deserializer, ser::Serializer, Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use zip::result::ZipResult;
+use zip::ZipArchive;
+
+/// Zip extra field holding an entry's original path bytes when its name is not UTF-8
+pub const RAW_PATH_EXTRA_ID: u16 = 0x7053;
+
+/// Prefix marking a source path string that carries escaped raw bytes
+const RAW_SOURCE_PREFIX: &str = "raw:";
+
+/// The platform bytes of a path. Windows names are always Unicode, so they are UTF-8 there.
+#[cfg(unix)]
+pub fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+pub fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+        Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+    }
+}
+
+#[cfg(unix)]
+pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(OsString::from(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+/// Percent-escape `%` and every byte that is not part of valid UTF-8.
+fn escape_bytes(bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        escaped.push_str(&chunk.valid().replace('%', "%25"));
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    escaped
+}
+
+fn unescape_bytes(escaped: &str) -> Vec<u8> {
+    let bytes = escaped.as_bytes();
+    let mut raw = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                raw.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                raw.push(byte);
+                i += 1;
+            }
+        }
+    }
+    raw
+}
+
+/// String form of a path for places that only hold text, such as `BackupSet::sources`.
+/// UTF-8 paths are returned unchanged; others are escaped behind a `raw:` prefix.
+pub fn encode_path(path: &Path) -> String {
+    match path.to_str() {
+        Some(s) => s.to_string(),
+        None => format!("{}{}", RAW_SOURCE_PREFIX, escape_bytes(&path_bytes(path))),
+    }
+}
+
+/// Inverse of `encode_path`.
+pub fn decode_path(encoded: &str) -> PathBuf {
+    match encoded.strip_prefix(RAW_SOURCE_PREFIX) {
+        Some(escaped) => path_from_bytes(unescape_bytes(escaped)),
+        None => PathBuf::from(encoded),
+    }
+}
+
+/// Zip entry names for the relative paths of one archive, each with the raw bytes to store
+/// alongside it when the name is not UTF-8. Such names are escaped so the entry still reads
+/// sensibly in other tools, and numbered when the escaped form is already a name in the
+/// archive, e.g. a UTF-8 file literally called `a%FF` next to the raw name `a\xFF`.
+/// Readers find entries by `entry_path`, never by name.
+pub fn entry_names(relative_paths: &[PathBuf]) -> Vec<(String, Option<Vec<u8>>)> {
+    let mut taken: HashSet<String> = relative_paths
+        .iter()
+        .filter_map(|p| p.to_str().map(str::to_string))
+        .collect();
+
+    relative_paths
+        .iter()
+        .map(|path| match path.to_str() {
+            Some(name) => (name.to_string(), None),
+            None => {
+                let raw = path_bytes(path).into_owned();
+                let escaped = escape_bytes(&raw);
+                let mut name = escaped.clone();
+                let mut n = 1;
+                while !taken.insert(name.clone()) {
+                    name = format!("{}~{}", escaped, n);
+                    n += 1;
+                }
+                (name, Some(raw))
+            }
+        })
+        .collect()
+}
+
+/// Recover the original relative path of a zip entry from its name and extra field block.
+pub fn entry_path(name: &str, extra_data: Option<&[u8]>) -> PathBuf {
+    extra_data
+        .and_then(|extra| find_extra_field(extra, RAW_PATH_EXTRA_ID))
+        .map(|raw| path_from_bytes(raw.to_vec()))
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+/// Index of every entry of `archive` by its original relative path, as `entry_path` reads it.
+pub fn entry_indices<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> ZipResult<HashMap<PathBuf, usize>> {
+    let mut indices = HashMap::with_capacity(archive.len());
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index)?;
+        indices.insert(entry_path(entry.name(), entry.extra_data()), index);
+    }
+    Ok(indices)
+}
+
+/// Find a field in a zip extra data block of `[id: u16][len: u16][data]` records.
+fn find_extra_field(mut extra: &[u8], header_id: u16) -> Option<&[u8]> {
+    while extra.len() >= 4 {
+        let id = u16::from_le_bytes([extra[0], extra[1]]);
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let data = extra.get(4..4 + len)?;
+        if id == header_id {
+            return Some(data);
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+/// Path representation in JSON: a plain string when UTF-8, otherwise `{"raw": [bytes]}`.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum StoredPath<'a> {
+    Text(Cow<'a, str>),
+    Raw { raw: Vec<u8> },
+}
+
+/// Serde adapter for `PathBuf` fields that must survive non-UTF-8 names.
+pub mod serde_path {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        match path.to_str() {
+            Some(s) => StoredPath::Text(Cow::Borrowed(s)),
+            None => StoredPath::Raw {
+                raw: path_bytes(path).into_owned(),
+            },
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        Ok(match StoredPath::deserialize(deserializer)? {
+            StoredPath::Text(s) => PathBuf::from(s.into_owned()),
+            StoredPath::Raw { raw } => path_from_bytes(raw),
+        })
+    }
+}
@@ -0,0 +1,336 @@
+//! Recovery - Rebuilding the local catalog from the backups themselves
+//! Used after a disk failure or on a new machine, when backups exist but Sentry has no record of them
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use uuid::Uuid;
+use zip::ZipArchive;
+
+use super::descriptor::ArchiveDescriptor;
+use super::engine::BackupError;
+use super::manifest::{
+    BackupManifest, FileEntry, IndexRebuild, ManifestManager, MANIFEST_SCHEMA_VERSION,
+};
+use super::rawpath::{encode_path, entry_path};
+use super::set::{BackupMode, BackupSet, BackupSetManager};
+
+/// A manifest or archive that was found but could not be recovered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryFailure {
+    /// File name at the recovery location
+    pub name: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryReport {
+    /// Backup versions found at the location
+    pub manifests_found: usize,
+    /// Versions added to the local store; the others were already known
+    pub manifests_imported: usize,
+    pub failures: Vec<RecoveryFailure>,
+    /// Result of re-indexing every local manifest afterwards
+    pub index: IndexRebuild,
+    /// Sets recreated for backups whose set no longer exists
+    pub backup_sets_created: Vec<BackupSet>,
+}
+
+/// Backups found at a recovery location.
+#[derive(Debug, Default)]
+pub struct FoundBackups {
+    pub manifests: Vec<BackupManifest>,
+    /// Set names recorded in self-describing archives, by set id
+    pub set_names: HashMap<String, String>,
+    pub failures: Vec<RecoveryFailure>,
+}
+
+/// Store the found manifests locally, re-index, and recreate the sets they belong to.
+/// `origin` describes where they were found, for the description of recreated sets.
+pub fn recover_manifests(
+    manifest_manager: &ManifestManager,
+    sets: &mut BackupSetManager,
+    found: FoundBackups,
+    origin: &str,
+) -> Result<RecoveryReport, BackupError> {
+    let FoundBackups {
+        manifests,
+        set_names,
+        mut failures,
+    } = found;
+    let manifests_found = manifests.len() + failures.len();
+    let mut manifests_imported = 0;
+    for manifest in &manifests {
+        match manifest_manager.import_manifest(manifest) {
+            Ok(true) => manifests_imported += 1,
+            Ok(false) => {}
+            Err(e) => failures.push(RecoveryFailure {
+                name: manifest.id.clone(),
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    let index = manifest_manager.rebuild_index()?;
+    let backup_sets_created = recreate_backup_sets(sets, &manifests, &set_names, origin);
+
+    Ok(RecoveryReport {
+        manifests_found,
+        manifests_imported,
+        failures,
+        index,
+        backup_sets_created,
+    })
+}
+
+/// Add a set for every set id in `manifests` that `sets` does not know. Recreated sets
+/// keep the original id, so their versions show up under them, and start disabled until
+/// their sources are checked on this machine.
+pub fn recreate_backup_sets(
+    sets: &mut BackupSetManager,
+    manifests: &[BackupManifest],
+    set_names: &HashMap<String, String>,
+    origin: &str,
+) -> Vec<BackupSet> {
+    let mut by_set: BTreeMap<&str, Vec<&BackupManifest>> = BTreeMap::new();
+    for manifest in manifests {
+        by_set
+            .entry(manifest.backup_set_id.as_str())
+            .or_default()
+            .push(manifest);
+    }
+
+    let mut created = Vec::new();
+    for (set_id, versions) in by_set {
+        if sets.get_set(set_id).is_some() {
+            continue;
+        }
+        let Some(latest) = versions.iter().max_by_key(|m| m.created_at) else {
+            continue;
+        };
+
+        let name = set_names
+            .get(set_id)
+            .cloned()
+            .unwrap_or_else(|| recovered_set_name(set_id, latest));
+        let mut set = BackupSet::new(name);
+        set.id = set_id.to_string();
+        set.description = Some(format!(
+            "Recreated from backups found in {} on {}",
+            origin,
+            Utc::now().format("%Y-%m-%d")
+        ));
+        set.sources = latest
+            .sources
+            .iter()
+            .map(|s| encode_path(&s.path))
+            .collect();
+        set.paths = set.sources.clone();
+        set.enabled = false;
+        // Keep every recovered version until the user sets a retention policy
+        set.retention_days = None;
+        set.max_versions = None;
+        set.mode = latest.mode;
+        set.cloud_upload = versions.iter().any(|m| m.cloud_location.is_some());
+        set.local_destination = latest
+            .archive_path
+            .as_deref()
+            .and_then(Path::parent)
+            .map(encode_path);
+        set.last_backup = Some(latest.created_at);
+        set.total_backups = versions.len() as u64;
+        set.total_size_backed_up = versions.iter().map(|m| m.total_size).sum();
+
+        sets.add_set(set.clone());
+        created.push(set);
+    }
+    created
+}
+
+/// Name for a recreated set whose name was not recorded: its source folders, or its id.
+fn recovered_set_name(set_id: &str, latest: &BackupManifest) -> String {
+    let folders: Vec<String> = latest
+        .sources
+        .iter()
+        .filter_map(|s| s.path.file_name())
+        .map(|n| n.to_string_lossy().into_owned())
+        .collect();
+    if folders.is_empty() {
+        let short_id: String = set_id.chars().take(8).collect();
+        format!("Recovered backup {}", short_id)
+    } else {
+        format!("Recovered: {}", folders.join(", "))
+    }
+}
+
+/// Read every `{set_id}_{uuid}.zip` archive directly inside `dir`, using the manifest
+/// embedded in self-describing archives. Other files are ignored; archives that cannot be
+/// read are reported as failures.
+pub fn scan_archive_folder(dir: &Path) -> Result<FoundBackups, BackupError> {
+    let mut found = FoundBackups::default();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Some((set_id, archive_id)) = parse_archive_name(name) else {
+            continue;
+        };
+        let read = ArchiveDescriptor::read(&path).and_then(|descriptor| match descriptor {
+            Some(descriptor) => {
+                found.set_names.insert(
+                    descriptor.manifest.backup_set_id.clone(),
+                    descriptor.backup_set_name,
+                );
+                Ok(descriptor.manifest)
+            }
+            None => manifest_from_archive(&path, set_id, archive_id),
+        });
+        match read {
+            Ok(manifest) => found.manifests.push(manifest),
+            Err(e) => {
+                eprintln!("Failed to read archive {}: {}", path.display(), e);
+                found.failures.push(RecoveryFailure {
+                    name: name.to_string(),
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+
+    found.manifests.sort_by_key(|m| m.created_at);
+    Ok(found)
+}
+
+/// Split `{set_id}_{uuid}.zip` into the set id and the archive id.
+fn parse_archive_name(name: &str) -> Option<(&str, &str)> {
+    let stem = name.strip_suffix(".zip")?;
+    let (set_id, archive_id) = stem.rsplit_once('_')?;
+    if set_id.is_empty() || Uuid::parse_str(archive_id).is_err() {
+        return None;
+    }
+    Some((set_id, archive_id))
+}
+
+/// Reconstruct a manifest from the entries of an archive written before archives carried
+/// their manifest. Each archive becomes a standalone full version; where its files came
+/// from is not recorded in the archive, so they can only be restored into a chosen folder.
+pub fn manifest_from_archive(
+    path: &Path,
+    set_id: &str,
+    archive_id: &str,
+) -> Result<BackupManifest, BackupError> {
+    let metadata = fs::metadata(path)?;
+    let written_at: DateTime<Utc> = metadata.modified()?.into();
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+
+    let mut entries = Vec::with_capacity(archive.len());
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let archived_path = entry_path(entry.name(), entry.extra_data());
+        let modified = entry
+            .last_modified()
+            .and_then(zip_time)
+            .unwrap_or(written_at);
+
+        let mut hasher = Sha256::new();
+        let mut buffer = [0u8; 8192];
+        let mut size = 0u64;
+        loop {
+            let bytes_read = entry.read(&mut buffer)?;
+            if bytes_read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..bytes_read]);
+            size += bytes_read as u64;
+        }
+        entries.push((
+            archived_path,
+            size,
+            format!("{:x}", hasher.finalize()),
+            modified,
+        ));
+    }
+
+    let labelled =
+        !entries.is_empty() && entries.iter().all(|(path, ..)| split_label(path).is_some());
+    let files: Vec<FileEntry> = entries
+        .into_iter()
+        .map(|(archived_path, size, hash, modified)| {
+            let (source_label, relative_path) = match split_label(&archived_path) {
+                Some((label, rest)) if labelled => (Some(label), rest),
+                _ => (None, archived_path.clone()),
+            };
+            FileEntry {
+                path: archived_path,
+                relative_path,
+                size,
+                hash,
+                modified,
+                backed_up_at: Some(written_at),
+                source_label,
+            }
+        })
+        .collect();
+
+    Ok(BackupManifest {
+        schema_version: MANIFEST_SCHEMA_VERSION,
+        id: archive_id.to_string(),
+        backup_set_id: set_id.to_string(),
+        created_at: written_at,
+        mode: BackupMode::Full,
+        parent_id: None,
+        total_size: files.iter().map(|f| f.size).sum(),
+        files,
+        compressed_size: metadata.len(),
+        cloud_location: None,
+        retention_until: None,
+        archive_path: Some(path.to_path_buf()),
+        merged_from: vec![],
+        superseded_by: None,
+        pinned: false,
+        label: Some("Recovered from archive contents".to_string()),
+        skipped_files: vec![],
+        sources: vec![],
+        deleted_files: vec![],
+    })
+}
+
+/// Split off a leading source label, a folder name followed by `-` and eight hex digits.
+fn split_label(path: &Path) -> Option<(String, PathBuf)> {
+    let mut components = path.components();
+    let Some(Component::Normal(first)) = components.next() else {
+        return None;
+    };
+    let rest = components.as_path();
+    if rest.as_os_str().is_empty() {
+        return None;
+    }
+    let label = first.to_str()?;
+    let (name, digest) = label.rsplit_once('-')?;
+    if name.is_empty() || digest.len() != 8 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some((label.to_string(), rest.to_path_buf()))
+}
+
+/// Zip timestamps are in the local time of the machine that wrote them.
+fn zip_time(time: zip::DateTime) -> Option<DateTime<Utc>> {
+    NaiveDate::from_ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32)?
+        .and_hms_opt(
+            time.hour() as u32,
+            time.minute() as u32,
+            time.second() as u32,
+        )?
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
@@ -0,0 +1,242 @@
+//! Backup Retention - Decides which backup versions have expired and prunes them
+//! Enforces `max_versions`, `retention_days` and GFS tiers without breaking backup chains
+
+use chrono::{DateTime, Datelike, Duration, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use super::engine::BackupError;
+use super::manifest::{ManifestManager, ManifestSummary};
+use super::set::{BackupSet, GfsPolicy};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneReason {
+    Expired,
+    ExceedsMaxVersions,
+    Superseded,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PruneCandidate {
+    pub manifest: ManifestSummary,
+    pub reason: PruneReason,
+}
+
+/// Why a version survives a retention pass; a version may have several reasons.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeepReason {
+    Pinned,
+    Latest,
+    WithinMaxVersions,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+    RequiredByChain,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetainedVersion {
+    pub manifest: ManifestSummary,
+    pub reasons: Vec<KeepReason>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionPlan {
+    pub backup_set_id: String,
+    pub keep: Vec<RetainedVersion>,
+    pub prune: Vec<PruneCandidate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrunedVersion {
+    pub manifest_id: String,
+    pub reason: PruneReason,
+    pub archive_path: Option<PathBuf>,
+}
+
+/// Maps a timestamp to the calendar period a GFS tier buckets it into.
+type PeriodKey = fn(DateTime<Local>) -> (i32, u32);
+
+/// Select the newest version of each of the most recent periods for every GFS tier.
+/// `versions` must be sorted newest first.
+fn gfs_selection(
+    policy: &GfsPolicy,
+    versions: &[&ManifestSummary],
+) -> HashMap<String, Vec<KeepReason>> {
+    let tiers: [(u32, KeepReason, PeriodKey); 4] = [
+        (policy.daily, KeepReason::Daily, |t| (t.year(), t.ordinal())),
+        (policy.weekly, KeepReason::Weekly, |t| {
+            (t.iso_week().year(), t.iso_week().week())
+        }),
+        (policy.monthly, KeepReason::Monthly, |t| {
+            (t.year(), t.month())
+        }),
+        (policy.yearly, KeepReason::Yearly, |t| (t.year(), 0)),
+    ];
+
+    let mut selected: HashMap<String, Vec<KeepReason>> = HashMap::new();
+    for (count, reason, period) in tiers {
+        let mut periods = HashSet::new();
+        for version in versions {
+            if periods.len() >= count as usize {
+                break;
+            }
+            if periods.insert(period(version.created_at.with_timezone(&Local))) {
+                selected
+                    .entry(version.id.clone())
+                    .or_default()
+                    .push(reason.clone());
+            }
+        }
+    }
+
+    selected
+}
+
+/// Work out which versions of a set should be kept or pruned at `now`, without touching anything.
+/// A version is kept when it is pinned, is the newest, falls within `max_versions` and
+/// `retention_days`, is selected by a GFS tier, or is an ancestor that a kept version depends on.
+pub fn plan_retention(
+    backup_set: &BackupSet,
+    manifest_manager: &ManifestManager,
+    now: DateTime<Utc>,
+) -> Result<RetentionPlan, BackupError> {
+    let mut versions = manifest_manager.list_manifests_for_set(&backup_set.id)?;
+    versions.sort_by_key(|v| Reverse(v.created_at));
+
+    // Superseded versions are replaced by a synthetic full and never count as restore points
+    let restore_points: Vec<&ManifestSummary> = versions
+        .iter()
+        .filter(|v| v.superseded_by.is_none())
+        .collect();
+    let mut keep: HashMap<String, Vec<KeepReason>> = backup_set
+        .gfs_policy
+        .as_ref()
+        .map(|policy| gfs_selection(policy, &restore_points))
+        .unwrap_or_default();
+    let mut prune: HashMap<String, PruneReason> = HashMap::new();
+
+    for (position, version) in restore_points.iter().enumerate() {
+        let expired = backup_set
+            .retention_days
+            .map(|days| version.created_at + Duration::days(days as i64) < now)
+            .unwrap_or(false);
+        let over_limit = backup_set
+            .max_versions
+            .map(|max| position >= max as usize)
+            .unwrap_or(false);
+
+        let reasons = keep.entry(version.id.clone()).or_default();
+        if position == 0 {
+            reasons.push(KeepReason::Latest);
+        } else if !expired && !over_limit {
+            reasons.push(KeepReason::WithinMaxVersions);
+        }
+
+        if reasons.is_empty() {
+            keep.remove(&version.id);
+            let reason = if expired {
+                PruneReason::Expired
+            } else {
+                PruneReason::ExceedsMaxVersions
+            };
+            prune.insert(version.id.clone(), reason);
+        }
+    }
+
+    for version in versions.iter().filter(|v| v.superseded_by.is_some()) {
+        prune.insert(version.id.clone(), PruneReason::Superseded);
+    }
+
+    // Pins override every other rule
+    for version in versions.iter().filter(|v| v.pinned) {
+        prune.remove(&version.id);
+        keep.entry(version.id.clone())
+            .or_default()
+            .insert(0, KeepReason::Pinned);
+    }
+
+    // Incremental and differential versions are useless without their parents
+    let parents: HashMap<&str, Option<&str>> = versions
+        .iter()
+        .map(|v| (v.id.as_str(), v.parent_id.as_deref()))
+        .collect();
+    let kept_ids: Vec<String> = keep.keys().cloned().collect();
+    for id in kept_ids {
+        let mut parent = parents.get(id.as_str()).copied().flatten();
+        while let Some(parent_id) = parent {
+            let reasons = keep.entry(parent_id.to_string()).or_default();
+            if reasons.contains(&KeepReason::RequiredByChain) {
+                break;
+            }
+            reasons.push(KeepReason::RequiredByChain);
+            prune.remove(parent_id);
+            parent = parents.get(parent_id).copied().flatten();
+        }
+    }
+
+    let mut plan = RetentionPlan {
+        backup_set_id: backup_set.id.clone(),
+        keep: vec![],
+        prune: vec![],
+    };
+    for manifest in versions {
+        if let Some(reason) = prune.remove(&manifest.id) {
+            plan.prune.push(PruneCandidate { manifest, reason });
+        } else {
+            let reasons = keep.remove(&manifest.id).unwrap_or_default();
+            plan.keep.push(RetainedVersion { manifest, reasons });
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Remove the local archive and manifest of each candidate, updating the index.
+/// Cloud copies must already be gone so the catalog never points at missing data.
+pub fn prune_local(
+    manifest_manager: &ManifestManager,
+    candidates: &[PruneCandidate],
+) -> Result<Vec<PrunedVersion>, BackupError> {
+    let mut pruned = Vec::new();
+
+    for candidate in candidates {
+        let Some(manifest) = manifest_manager.load_manifest_by_id(&candidate.manifest.id)? else {
+            continue;
+        };
+
+        // The version may have been pinned after the plan was made
+        if manifest.pinned {
+            println!("Retention: skipped pinned backup {}", manifest.id);
+            continue;
+        }
+        let archive_path = manifest.archive_path;
+
+        if let Some(path) = archive_path.as_ref().filter(|p| p.exists()) {
+            fs::remove_file(path)?;
+        }
+        manifest_manager.delete_manifest(&candidate.manifest.id)?;
+
+        println!(
+            "Retention: pruned backup {} of set {} ({:?}, created {})",
+            candidate.manifest.id,
+            candidate.manifest.backup_set_id,
+            candidate.reason,
+            candidate.manifest.created_at
+        );
+
+        pruned.push(PrunedVersion {
+            manifest_id: candidate.manifest.id.clone(),
+            reason: candidate.reason.clone(),
+            archive_path,
+        });
+    }
+
+    Ok(pruned)
+}
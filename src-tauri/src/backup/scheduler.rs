@@ -8,6 +8,8 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+use super::set::BackupMode;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum ScheduleType {
@@ -50,12 +52,20 @@ pub struct Schedule {
     pub weather_triggers: Vec<WeatherTrigger>,
     pub last_run: Option<DateTime<Utc>>,
     pub next_run: Option<DateTime<Utc>>,
+    /// Mode of the runs this schedule starts; `None` follows the backup set's mode
+    #[serde(default = "legacy_schedule_mode")]
+    pub mode: Option<BackupMode>,
     #[serde(default = "Utc::now")]
     pub created_at: DateTime<Utc>,
     #[serde(default = "Utc::now")]
     pub updated_at: DateTime<Utc>,
 }
 
+/// Schedules saved before backup modes always ran full backups, and keep doing so.
+fn legacy_schedule_mode() -> Option<BackupMode> {
+    Some(BackupMode::Full)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WeatherTrigger {
     pub alert_type: WeatherAlertType,
@@ -132,6 +142,7 @@ impl Schedule {
             weather_triggers: vec![],
             last_run: None,
             next_run: None,
+            mode: None,
             created_at: now,
             updated_at: now,
         }
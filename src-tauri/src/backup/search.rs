@@ -0,0 +1,259 @@
+//! File Search - Finds every stored version of a file across all manifests
+//! Covers local manifests and manifests cached from Drive
+
+use chrono::{DateTime, Utc};
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use super::engine::BackupError;
+use super::manifest::{BackupManifest, CloudLocation, FileEntry, ManifestManager};
+use super::rawpath::serde_path;
+use super::set::BackupMode;
+
+/// Files returned when the query sets no limit
+const DEFAULT_LIMIT: usize = 200;
+
+/// All criteria are optional and combined with AND. Text matches ignore case.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileSearchQuery {
+    /// Substring of the file name
+    pub name: Option<String>,
+    /// Glob such as `*.xlsx`; matched against the full path when it contains a separator,
+    /// otherwise against the file name
+    pub glob: Option<String>,
+    /// Substring of the original path
+    pub path: Option<String>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub modified_after: Option<DateTime<Utc>>,
+    pub modified_before: Option<DateTime<Utc>>,
+    /// Limits on when the version was backed up
+    pub backed_up_after: Option<DateTime<Utc>>,
+    pub backed_up_before: Option<DateTime<Utc>>,
+    pub backup_set_id: Option<String>,
+    /// Exact SHA-256 of the content, to find every copy of a file
+    pub hash: Option<String>,
+    /// Maximum number of distinct files returned
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestOrigin {
+    /// Recorded on this machine
+    Local,
+    /// Only known from a manifest cached from Drive
+    Cloud,
+}
+
+/// One stored copy of a file and where to get it back from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileVersion {
+    pub manifest_id: String,
+    pub backup_set_id: String,
+    pub backed_up_at: DateTime<Utc>,
+    pub mode: BackupMode,
+    pub origin: ManifestOrigin,
+    pub size: u64,
+    pub hash: String,
+    pub modified: DateTime<Utc>,
+    /// Path of the entry inside the archive
+    #[serde(with = "serde_path")]
+    pub archived_path: PathBuf,
+    pub archive_path: Option<PathBuf>,
+    pub archive_available: bool,
+    pub cloud_location: Option<CloudLocation>,
+}
+
+/// A file and all of its stored versions, newest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileHistory {
+    #[serde(with = "serde_path")]
+    pub path: PathBuf,
+    pub versions: Vec<FileVersion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSearchResult {
+    pub files: Vec<FileHistory>,
+    pub manifests_searched: usize,
+    /// More files matched than the limit allowed
+    pub truncated: bool,
+}
+
+struct Matcher {
+    query: FileSearchQuery,
+    name: Option<String>,
+    path: Option<String>,
+    glob: Option<(Pattern, bool)>,
+}
+
+impl Matcher {
+    fn new(query: &FileSearchQuery) -> Result<Self, BackupError> {
+        let glob = match query.glob.as_deref().filter(|g| !g.is_empty()) {
+            Some(glob) => {
+                let pattern = Pattern::new(glob).map_err(|e| {
+                    BackupError::InvalidPath(format!("Invalid pattern {}: {}", glob, e))
+                })?;
+                Some((pattern, glob.contains(['/', '\\'])))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            query: query.clone(),
+            name: query.name.as_ref().map(|n| n.to_lowercase()),
+            path: query.path.as_ref().map(|p| p.to_lowercase()),
+            glob,
+        })
+    }
+
+    fn matches_manifest(&self, manifest: &BackupManifest) -> bool {
+        let q = &self.query;
+        q.backup_set_id
+            .as_ref()
+            .is_none_or(|id| &manifest.backup_set_id == id)
+            && q.backed_up_after.is_none_or(|t| manifest.created_at >= t)
+            && q.backed_up_before.is_none_or(|t| manifest.created_at <= t)
+    }
+
+    fn matches_file(&self, file: &FileEntry) -> bool {
+        let q = &self.query;
+        if q.min_size.is_some_and(|min| file.size < min)
+            || q.max_size.is_some_and(|max| file.size > max)
+            || q.modified_after.is_some_and(|t| file.modified < t)
+            || q.modified_before.is_some_and(|t| file.modified > t)
+            || q.hash
+                .as_ref()
+                .is_some_and(|h| !h.is_empty() && !file.hash.eq_ignore_ascii_case(h))
+        {
+            return false;
+        }
+
+        let file_name = file_name(&file.path);
+        let full_path = file.path.to_string_lossy();
+        if let Some(name) = &self.name {
+            if !file_name.to_lowercase().contains(name) {
+                return false;
+            }
+        }
+        if let Some(path) = &self.path {
+            if !full_path.to_lowercase().contains(path) {
+                return false;
+            }
+        }
+        if let Some((pattern, whole_path)) = &self.glob {
+            let options = MatchOptions {
+                case_sensitive: false,
+                require_literal_separator: false,
+                require_literal_leading_dot: false,
+            };
+            let subject = if *whole_path {
+                full_path.replace('\\', "/")
+            } else {
+                file_name
+            };
+            if !pattern.matches_with(&subject, options) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Search every local manifest, through the catalog, and every manifest cached from Drive.
+/// A manifest known both ways is reported once, as local.
+pub fn search_files(
+    manifest_manager: &ManifestManager,
+    query: &FileSearchQuery,
+) -> Result<FileSearchResult, BackupError> {
+    let matcher = Matcher::new(query)?;
+    let mut by_path: BTreeMap<PathBuf, Vec<FileVersion>> = BTreeMap::new();
+
+    let local_ids: HashSet<String> = manifest_manager
+        .load_index()?
+        .manifests
+        .into_iter()
+        .map(|m| m.id)
+        .collect();
+    for found in manifest_manager.find_versions(query)? {
+        if !matcher.matches_file(&found.file) {
+            continue;
+        }
+        let manifest = found.manifest;
+        by_path
+            .entry(found.file.path.clone())
+            .or_default()
+            .push(FileVersion {
+                manifest_id: manifest.id,
+                backup_set_id: manifest.backup_set_id,
+                backed_up_at: manifest.created_at,
+                mode: manifest.mode,
+                origin: ManifestOrigin::Local,
+                size: found.file.size,
+                archived_path: found.file.archived_path(),
+                hash: found.file.hash,
+                modified: found.file.modified,
+                archive_available: found.archive_path.as_ref().is_some_and(|p| p.exists()),
+                archive_path: found.archive_path,
+                cloud_location: found.cloud_location,
+            });
+    }
+
+    let cloud_manifests: Vec<BackupManifest> = manifest_manager
+        .load_cached_cloud_manifests()?
+        .into_iter()
+        .filter(|m| !local_ids.contains(&m.id))
+        .collect();
+    for manifest in &cloud_manifests {
+        if !matcher.matches_manifest(manifest) {
+            continue;
+        }
+        for file in manifest.files.iter().filter(|f| matcher.matches_file(f)) {
+            by_path
+                .entry(file.path.clone())
+                .or_default()
+                .push(FileVersion {
+                    manifest_id: manifest.id.clone(),
+                    backup_set_id: manifest.backup_set_id.clone(),
+                    backed_up_at: manifest.created_at,
+                    mode: manifest.mode,
+                    origin: ManifestOrigin::Cloud,
+                    size: file.size,
+                    hash: file.hash.clone(),
+                    modified: file.modified,
+                    archived_path: file.archived_path(),
+                    archive_path: manifest.archive_path.clone(),
+                    archive_available: manifest.archive_path.as_ref().is_some_and(|p| p.exists()),
+                    cloud_location: manifest.cloud_location.clone(),
+                });
+        }
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    let truncated = by_path.len() > limit;
+    let files = by_path
+        .into_iter()
+        .take(limit)
+        .map(|(path, mut versions)| {
+            versions.sort_by_key(|v| Reverse(v.backed_up_at));
+            FileHistory { path, versions }
+        })
+        .collect();
+
+    Ok(FileSearchResult {
+        files,
+        manifests_searched: local_ids.len() + cloud_manifests.len(),
+        truncated,
+    })
+}
@@ -1,10 +1,65 @@
 //! Backup Set - Defines collections of paths to backup with settings
 
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize};
 use std::path::PathBuf;
 use uuid::Uuid;
 
+use super::rawpath::{decode_path, encode_path};
+
+/// How much of a backup set each run captures.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupMode {
+    /// Every file in the sources, independent of earlier runs.
+    #[serde(alias = "Full")]
+    Full,
+    /// Files changed since the previous run of any mode.
+    #[default]
+    #[serde(alias = "Incremental")]
+    Incremental,
+    /// Files changed since the last full backup.
+    #[serde(alias = "Differential")]
+    Differential,
+}
+
+/// Grandfather-father-son retention tiers.
+/// Each tier keeps the newest version from that many of the most recent days, weeks, months or years.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct GfsPolicy {
+    pub daily: u32,
+    pub weekly: u32,
+    pub monthly: u32,
+    pub yearly: u32,
+}
+
+/// What a run does with a file it cannot read, e.g. a locked database or a permission-denied path.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FileErrorPolicy {
+    /// Record the file as skipped and carry on.
+    #[default]
+    Skip,
+    /// Try again, doubling the wait after each failure, then skip the file.
+    Retry { attempts: u32, backoff_ms: u64 },
+    /// Abort the whole run.
+    Fail,
+}
+
+/// Accept the legacy `incremental: bool` flag as well as a `BackupMode` value.
+fn deserialize_mode<'de, D>(deserializer: D) -> Result<BackupMode, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+
+    match value {
+        serde_json::Value::Bool(true) => Ok(BackupMode::Incremental),
+        serde_json::Value::Bool(false) => Ok(BackupMode::Full),
+        other => BackupMode::deserialize(other).map_err(de::Error::custom),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BackupSet {
     pub id: String,
@@ -15,9 +70,16 @@ pub struct BackupSet {
     pub exclude_patterns: Vec<String>,
     pub enabled: bool,
     pub compression_level: u8,
-    pub incremental: bool,
+    #[serde(alias = "incremental", deserialize_with = "deserialize_mode", default)]
+    pub mode: BackupMode,
     pub retention_days: Option<u32>,
     pub max_versions: Option<u32>,
+    /// Tiered retention applied on top of `max_versions` and `retention_days`
+    #[serde(default)]
+    pub gfs_policy: Option<GfsPolicy>,
+    /// How unreadable files are handled during scanning and archiving
+    #[serde(default)]
+    pub file_error_policy: FileErrorPolicy,
     pub cloud_upload: bool,
     pub local_destination: Option<String>,
     pub created_at: DateTime<Utc>,
@@ -49,9 +111,11 @@ impl BackupSet {
             ],
             enabled: true,
             compression_level: 6,
-            incremental: true,
+            mode: BackupMode::Incremental,
             retention_days: Some(30),
             max_versions: Some(10),
+            gfs_policy: None,
+            file_error_policy: FileErrorPolicy::Skip,
             cloud_upload: false, // Default to local backups only
             local_destination: None,
             created_at: now,
@@ -63,14 +127,19 @@ impl BackupSet {
     }
 
     pub fn with_sources(mut self, sources: Vec<PathBuf>) -> Self {
-        let source_strings: Vec<String> = sources.into_iter().map(|p| p.to_string_lossy().to_string()).collect();
+        let source_strings: Vec<String> = sources.iter().map(|p| encode_path(p)).collect();
         self.sources = source_strings.clone();
         self.paths = source_strings;
         self
     }
 
+    /// Source folders as real paths, undoing the escaping of non-UTF-8 names.
+    pub fn source_paths(&self) -> Vec<PathBuf> {
+        self.sources.iter().map(|s| decode_path(s)).collect()
+    }
+
     pub fn add_source(&mut self, path: PathBuf) {
-        let path_str = path.to_string_lossy().to_string();
+        let path_str = encode_path(&path);
         if !self.sources.contains(&path_str) {
             self.sources.push(path_str.clone());
             self.paths.push(path_str);
@@ -79,7 +148,7 @@ impl BackupSet {
     }
 
     pub fn remove_source(&mut self, path: &PathBuf) {
-        let path_str = path.to_string_lossy().to_string();
+        let path_str = encode_path(path);
         self.sources.retain(|p| p != &path_str);
         self.paths.retain(|p| p != &path_str);
         self.updated_at = Utc::now();
@@ -0,0 +1,113 @@
+//! Archive Spool - Streams an archive to a remote sink one chunk at a time
+//! Only the chunk being assembled is kept on disk
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// Receives the finished chunks of a streamed archive in order.
+pub trait ChunkSink {
+    /// Every chunk except the last is exactly the spool's chunk size.
+    /// `total_size` is set only on the final chunk, once the archive length is known.
+    fn write_chunk(&mut self, data: &[u8], total_size: Option<u64>) -> io::Result<()>;
+}
+
+impl<T: ChunkSink + ?Sized> ChunkSink for &mut T {
+    fn write_chunk(&mut self, data: &[u8], total_size: Option<u64>) -> io::Result<()> {
+        (**self).write_chunk(data, total_size)
+    }
+}
+
+/// Size and checksum of an archive that was streamed instead of written to disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamedArchive {
+    pub size: u64,
+    pub hash: String,
+}
+
+/// `Write` adapter that hands an archive to a `ChunkSink` as it is produced.
+///
+/// Bytes collect in a spool file until a full chunk is there, which is sent right away, so
+/// the writer must never need to revisit them; `StreamingZipWriter` writes that way.
+pub struct SpoolWriter<S: ChunkSink> {
+    sink: S,
+    spool: File,
+    spool_path: PathBuf,
+    chunk_size: u64,
+    /// Bytes held in the spool
+    held: u64,
+    /// Archive length written so far
+    len: u64,
+    hasher: Sha256,
+}
+
+impl<S: ChunkSink> SpoolWriter<S> {
+    pub fn new(spool_path: PathBuf, chunk_size: usize, sink: S) -> io::Result<Self> {
+        let spool = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&spool_path)?;
+
+        Ok(Self {
+            sink,
+            spool,
+            spool_path,
+            chunk_size: chunk_size as u64,
+            held: 0,
+            len: 0,
+            hasher: Sha256::new(),
+        })
+    }
+
+    /// Read back everything held in the spool and empty it.
+    fn take_spooled(&mut self) -> io::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(self.held as usize);
+        self.spool.seek(SeekFrom::Start(0))?;
+        self.spool.read_to_end(&mut data)?;
+        self.spool.set_len(0)?;
+        self.spool.seek(SeekFrom::Start(0))?;
+        self.held = 0;
+        self.hasher.update(&data);
+        Ok(data)
+    }
+
+    /// Send the final chunk, remove the spool file and hand the sink back.
+    pub fn finish(mut self) -> io::Result<(S, StreamedArchive)> {
+        let remainder = self.take_spooled()?;
+        self.sink.write_chunk(&remainder, Some(self.len))?;
+
+        drop(self.spool);
+        let _ = fs::remove_file(&self.spool_path);
+
+        let archive = StreamedArchive {
+            size: self.len,
+            hash: format!("{:x}", self.hasher.finalize()),
+        };
+        Ok((self.sink, archive))
+    }
+}
+
+impl<S: ChunkSink> Write for SpoolWriter<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Never spool past the end of the chunk being assembled
+        let room = (self.chunk_size - self.held) as usize;
+        let written = self.spool.write(&buf[..buf.len().min(room)])?;
+        self.held += written as u64;
+        self.len += written as u64;
+
+        // A full chunk is final, so it can go now; the last one waits for the total size
+        if self.held == self.chunk_size {
+            let chunk = self.take_spooled()?;
+            self.sink.write_chunk(&chunk, None)?;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.spool.flush()
+    }
+}
@@ -0,0 +1,209 @@
+//! Backup Set Validation - Catches configurations that would misbehave when a set runs
+//! Destinations inside sources, overlapping sources and source folders that are missing
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::rawpath::{decode_path, encode_path};
+use super::set::BackupSet;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationCode {
+    /// The set has nothing to back up
+    NoSources,
+    /// The local destination is a source or lies inside one, so every run archives the last
+    DestinationInSource,
+    /// A source folder does not exist, e.g. a drive that is not connected
+    SourceMissing,
+    /// A source is a file rather than a folder
+    SourceNotDirectory,
+    /// The same folder is listed more than once
+    DuplicateSource,
+    /// A source lies inside another source, so its files are stored twice
+    NestedSource,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    pub code: ValidationCode,
+    /// The offending path, in the same form as `BackupSet::sources`
+    pub path: Option<String>,
+    /// The other path involved, e.g. the source that contains `path`
+    pub related_path: Option<String>,
+    pub message: String,
+}
+
+/// Errors block saving and running a set; warnings are shown but allowed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub errors: Vec<ValidationIssue>,
+    pub warnings: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// The errors joined into one message, for callers that can only return a string.
+    pub fn error_message(&self) -> Option<String> {
+        if self.errors.is_empty() {
+            return None;
+        }
+        Some(
+            self.errors
+                .iter()
+                .map(|e| e.message.as_str())
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+
+    fn error(
+        &mut self,
+        code: ValidationCode,
+        path: &Path,
+        related: Option<&Path>,
+        message: String,
+    ) {
+        self.errors.push(issue(code, path, related, message));
+    }
+
+    fn warning(
+        &mut self,
+        code: ValidationCode,
+        path: &Path,
+        related: Option<&Path>,
+        message: String,
+    ) {
+        self.warnings.push(issue(code, path, related, message));
+    }
+}
+
+fn issue(
+    code: ValidationCode,
+    path: &Path,
+    related: Option<&Path>,
+    message: String,
+) -> ValidationIssue {
+    ValidationIssue {
+        code,
+        path: Some(encode_path(path)),
+        related_path: related.map(encode_path),
+        message,
+    }
+}
+
+/// Absolute, link-free form of `path` for comparisons. The deepest existing ancestor is
+/// canonicalized and the rest re-appended, so destinations that don't exist yet still compare.
+fn resolve(path: &Path) -> PathBuf {
+    let mut rest = Vec::new();
+    let mut current = path;
+    loop {
+        if let Ok(canonical) = fs::canonicalize(current) {
+            return rest
+                .iter()
+                .rev()
+                .fold(canonical, |acc, name| acc.join(name));
+        }
+        match (current.parent(), current.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                current = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+/// Check a set's sources and destination before it is saved or run.
+pub fn validate_backup_set(backup_set: &BackupSet) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    let sources = backup_set.source_paths();
+    if sources.is_empty() {
+        report.errors.push(ValidationIssue {
+            code: ValidationCode::NoSources,
+            path: None,
+            related_path: None,
+            message: "Add at least one folder to back up".to_string(),
+        });
+    }
+
+    for source in &sources {
+        match fs::metadata(source) {
+            Ok(metadata) if !metadata.is_dir() => report.error(
+                ValidationCode::SourceNotDirectory,
+                source,
+                None,
+                format!("{} is a file, not a folder", source.display()),
+            ),
+            Ok(_) => {}
+            // Missing folders may be on a drive that is not plugged in, so saving is allowed
+            Err(_) => report.warning(
+                ValidationCode::SourceMissing,
+                source,
+                None,
+                format!("{} does not exist", source.display()),
+            ),
+        }
+    }
+
+    let resolved: Vec<PathBuf> = sources.iter().map(|s| resolve(s)).collect();
+    for (i, (source, path)) in sources.iter().zip(&resolved).enumerate() {
+        for (other_source, other_path) in sources.iter().zip(&resolved).take(i) {
+            if path == other_path {
+                report.warning(
+                    ValidationCode::DuplicateSource,
+                    source,
+                    Some(other_source),
+                    format!("{} is listed more than once", source.display()),
+                );
+            } else if path.starts_with(other_path) {
+                report.warning(
+                    ValidationCode::NestedSource,
+                    source,
+                    Some(other_source),
+                    format!(
+                        "{} is inside {} and will be backed up twice",
+                        source.display(),
+                        other_source.display()
+                    ),
+                );
+            } else if other_path.starts_with(path) {
+                report.warning(
+                    ValidationCode::NestedSource,
+                    other_source,
+                    Some(source),
+                    format!(
+                        "{} is inside {} and will be backed up twice",
+                        other_source.display(),
+                        source.display()
+                    ),
+                );
+            }
+        }
+    }
+
+    if let Some(destination) = backup_set.local_destination.as_deref().map(decode_path) {
+        let resolved_destination = resolve(&destination);
+        for (source, path) in sources.iter().zip(&resolved) {
+            if resolved_destination.starts_with(path) {
+                report.error(
+                    ValidationCode::DestinationInSource,
+                    &destination,
+                    Some(source),
+                    format!(
+                        "Backup destination {} is inside source {}, so each backup would include the previous ones",
+                        destination.display(),
+                        source.display()
+                    ),
+                );
+            }
+        }
+    }
+
+    report
+}
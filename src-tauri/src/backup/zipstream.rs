@@ -0,0 +1,510 @@
+//! Zip Stream - Writes zip archives front to back, without seeking
+//! Entry sizes and checksums follow the data in data descriptors, so every byte is final
+//! as soon as it is written and can be handed to a `ChunkSink` straight away
+
+use flate2::{Compress, Compression, Crc, FlushCompress, Status};
+use std::fs::File;
+use std::io::{self, Seek, Write};
+use zip::write::FullFileOptions;
+use zip::ZipWriter;
+
+use super::engine::BackupError;
+use super::rawpath::RAW_PATH_EXTRA_ID;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x0807_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const ZIP64_END_SIGNATURE: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const END_SIGNATURE: u32 = 0x0605_4b50;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+
+/// Sizes and offsets at or above this need zip64 fields
+const ZIP64_THRESHOLD: u64 = u32::MAX as u64;
+/// Sizes are in a data descriptor, names are UTF-8
+const FLAGS: u16 = 0x0008 | 0x0800;
+const METHOD_DEFLATED: u16 = 8;
+/// Unix, zip 4.5
+const VERSION_MADE_BY: u16 = (3 << 8) | 45;
+/// Regular file, rw-r--r--
+const EXTERNAL_ATTRIBUTES: u32 = 0o100644 << 16;
+
+/// The entry operations `BackupEngine::write_archive` needs, so the same code can write an
+/// archive to disk with `ZipWriter` or stream it with `StreamingZipWriter`.
+pub trait EntryWriter: Write {
+    type Output;
+
+    /// Start a deflated entry; `raw_name` holds the original bytes of an escaped name and
+    /// `large` requests zip64 sizes for entries that may exceed 4 GiB.
+    fn start_entry(
+        &mut self,
+        name: &str,
+        raw_name: Option<Vec<u8>>,
+        large: bool,
+    ) -> Result<(), BackupError>;
+
+    /// Drop the entry being written so it is not listed in the archive.
+    fn abort_entry(&mut self) -> Result<(), BackupError>;
+
+    fn finish_archive(self) -> Result<Self::Output, BackupError>;
+}
+
+impl<W: Write + Seek> EntryWriter for ZipWriter<W> {
+    type Output = W;
+
+    fn start_entry(
+        &mut self,
+        name: &str,
+        raw_name: Option<Vec<u8>>,
+        large: bool,
+    ) -> Result<(), BackupError> {
+        let mut options = FullFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .compression_level(Some(6))
+            .large_file(large);
+        if let Some(raw_name) = raw_name {
+            options.add_extra_data(RAW_PATH_EXTRA_ID, raw_name.into(), false)?;
+        }
+        self.start_file(name, options)?;
+        Ok(())
+    }
+
+    fn abort_entry(&mut self) -> Result<(), BackupError> {
+        self.abort_file()?;
+        Ok(())
+    }
+
+    fn finish_archive(self) -> Result<W, BackupError> {
+        Ok(self.finish()?)
+    }
+}
+
+/// `ZipWriter` over a local archive file that also cuts aborted entries off the file.
+/// `ZipWriter` only rewinds over them, so the bytes would otherwise stay as dead data in
+/// the middle of the archive once later entries and the central directory are written.
+pub struct ArchiveFileWriter {
+    zip: ZipWriter<File>,
+    /// Duplicate handle sharing the zip writer's file offset
+    file: File,
+}
+
+impl ArchiveFileWriter {
+    pub fn new(file: File) -> io::Result<Self> {
+        Ok(Self {
+            file: file.try_clone()?,
+            zip: ZipWriter::new(file),
+        })
+    }
+}
+
+impl Write for ArchiveFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.zip.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.zip.flush()
+    }
+}
+
+impl EntryWriter for ArchiveFileWriter {
+    type Output = File;
+
+    fn start_entry(
+        &mut self,
+        name: &str,
+        raw_name: Option<Vec<u8>>,
+        large: bool,
+    ) -> Result<(), BackupError> {
+        self.zip.start_entry(name, raw_name, large)
+    }
+
+    fn abort_entry(&mut self) -> Result<(), BackupError> {
+        // The zip writer leaves the cursor at the aborted entry's header
+        self.zip.abort_entry()?;
+        let header_start = self.file.stream_position()?;
+        self.file.set_len(header_start)?;
+        Ok(())
+    }
+
+    fn finish_archive(self) -> Result<File, BackupError> {
+        self.zip.finish_archive()
+    }
+}
+
+/// An entry that has been started, with what the data descriptor and central directory need.
+struct Entry {
+    name: String,
+    raw_name: Option<Vec<u8>>,
+    large: bool,
+    header_offset: u64,
+    data_offset: u64,
+    time: u16,
+    date: u16,
+    crc: Crc,
+    size: u64,
+}
+
+/// A finished entry, as listed in the central directory.
+struct CentralEntry {
+    name: String,
+    raw_name: Option<Vec<u8>>,
+    large: bool,
+    header_offset: u64,
+    time: u16,
+    date: u16,
+    crc: u32,
+    compressed_size: u64,
+    size: u64,
+}
+
+/// Zip writer for outputs that cannot seek. Every entry is deflated and followed by a data
+/// descriptor; an aborted entry stays in the output as unreferenced bytes.
+pub struct StreamingZipWriter<W: Write> {
+    inner: W,
+    /// Bytes written to `inner`
+    offset: u64,
+    compress: Compress,
+    buffer: Vec<u8>,
+    current: Option<Entry>,
+    entries: Vec<CentralEntry>,
+}
+
+impl<W: Write> StreamingZipWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            compress: Compress::new(Compression::new(6), false),
+            buffer: vec![0u8; 64 * 1024],
+            current: None,
+            entries: Vec::new(),
+        }
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.inner.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Run `input` through the compressor and write what comes out.
+    fn deflate(&mut self, mut input: &[u8], flush: FlushCompress) -> io::Result<()> {
+        loop {
+            let (in_before, out_before) = (self.compress.total_in(), self.compress.total_out());
+            let status = self
+                .compress
+                .compress(input, &mut self.buffer, flush)
+                .map_err(io::Error::other)?;
+            let consumed = (self.compress.total_in() - in_before) as usize;
+            let produced = (self.compress.total_out() - out_before) as usize;
+            input = &input[consumed..];
+
+            self.inner.write_all(&self.buffer[..produced])?;
+            self.offset += produced as u64;
+
+            let done = match flush {
+                FlushCompress::Finish => status == Status::StreamEnd,
+                _ => input.is_empty() && produced < self.buffer.len(),
+            };
+            if done {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Complete the current entry with its data descriptor.
+    fn finish_entry(&mut self) -> Result<(), BackupError> {
+        if self.current.is_none() {
+            return Ok(());
+        }
+        self.deflate(&[], FlushCompress::Finish)?;
+        let Some(entry) = self.current.take() else {
+            return Ok(());
+        };
+
+        let compressed_size = self.offset - entry.data_offset;
+        if !entry.large && (entry.size >= ZIP64_THRESHOLD || compressed_size >= ZIP64_THRESHOLD) {
+            return Err(BackupError::Io(io::Error::other(format!(
+                "{} grew past 4 GiB while it was archived",
+                entry.name
+            ))));
+        }
+        let crc = entry.crc.sum();
+
+        let mut descriptor = Vec::with_capacity(24);
+        put_u32(&mut descriptor, DATA_DESCRIPTOR_SIGNATURE);
+        put_u32(&mut descriptor, crc);
+        if entry.large {
+            put_u64(&mut descriptor, compressed_size);
+            put_u64(&mut descriptor, entry.size);
+        } else {
+            put_u32(&mut descriptor, compressed_size as u32);
+            put_u32(&mut descriptor, entry.size as u32);
+        }
+        self.write_raw(&descriptor)?;
+
+        self.entries.push(CentralEntry {
+            name: entry.name,
+            raw_name: entry.raw_name,
+            large: entry.large,
+            header_offset: entry.header_offset,
+            time: entry.time,
+            date: entry.date,
+            crc,
+            compressed_size,
+            size: entry.size,
+        });
+        Ok(())
+    }
+
+    fn central_header(entry: &CentralEntry) -> Vec<u8> {
+        // Zip64 fields appear in this order, and only for values that do not fit
+        let mut zip64 = Vec::new();
+        if entry.size >= ZIP64_THRESHOLD {
+            put_u64(&mut zip64, entry.size);
+        }
+        if entry.compressed_size >= ZIP64_THRESHOLD {
+            put_u64(&mut zip64, entry.compressed_size);
+        }
+        if entry.header_offset >= ZIP64_THRESHOLD {
+            put_u64(&mut zip64, entry.header_offset);
+        }
+
+        let mut extra = Vec::new();
+        if !zip64.is_empty() {
+            put_u16(&mut extra, ZIP64_EXTRA_ID);
+            put_u16(&mut extra, zip64.len() as u16);
+            extra.extend_from_slice(&zip64);
+        }
+        if let Some(raw_name) = &entry.raw_name {
+            put_extra_field(&mut extra, RAW_PATH_EXTRA_ID, raw_name);
+        }
+
+        let needs_zip64 = entry.large || !zip64.is_empty();
+        let mut header = Vec::with_capacity(46 + entry.name.len() + extra.len());
+        put_u32(&mut header, CENTRAL_HEADER_SIGNATURE);
+        put_u16(&mut header, VERSION_MADE_BY);
+        put_u16(&mut header, if needs_zip64 { 45 } else { 20 });
+        put_u16(&mut header, FLAGS);
+        put_u16(&mut header, METHOD_DEFLATED);
+        put_u16(&mut header, entry.time);
+        put_u16(&mut header, entry.date);
+        put_u32(&mut header, entry.crc);
+        put_u32(
+            &mut header,
+            entry.compressed_size.min(ZIP64_THRESHOLD) as u32,
+        );
+        put_u32(&mut header, entry.size.min(ZIP64_THRESHOLD) as u32);
+        put_u16(&mut header, entry.name.len() as u16);
+        put_u16(&mut header, extra.len() as u16);
+        put_u16(&mut header, 0); // comment length
+        put_u16(&mut header, 0); // disk number
+        put_u16(&mut header, 0); // internal attributes
+        put_u32(&mut header, EXTERNAL_ATTRIBUTES);
+        put_u32(&mut header, entry.header_offset.min(ZIP64_THRESHOLD) as u32);
+        header.extend_from_slice(entry.name.as_bytes());
+        header.extend_from_slice(&extra);
+        header
+    }
+}
+
+impl<W: Write> Write for StreamingZipWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(entry) = self.current.as_mut() else {
+            return Err(io::Error::other("no zip entry has been started"));
+        };
+        entry.crc.update(buf);
+        entry.size += buf.len() as u64;
+        self.deflate(buf, FlushCompress::None)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> EntryWriter for StreamingZipWriter<W> {
+    type Output = W;
+
+    fn start_entry(
+        &mut self,
+        name: &str,
+        raw_name: Option<Vec<u8>>,
+        large: bool,
+    ) -> Result<(), BackupError> {
+        self.finish_entry()?;
+        if self.entries.iter().any(|e| e.name == name) {
+            return Err(zip::result::ZipError::InvalidArchive("Duplicate filename").into());
+        }
+
+        let mut extra = Vec::new();
+        if large {
+            // Sizes are only known at the end, so the local zip64 field holds zeros
+            put_u16(&mut extra, ZIP64_EXTRA_ID);
+            put_u16(&mut extra, 16);
+            put_u64(&mut extra, 0);
+            put_u64(&mut extra, 0);
+        }
+        if let Some(raw_name) = &raw_name {
+            put_extra_field(&mut extra, RAW_PATH_EXTRA_ID, raw_name);
+        }
+
+        let modified = zip::DateTime::default_for_write();
+        let (time, date) = (modified.timepart(), modified.datepart());
+        let placeholder = if large { u32::MAX } else { 0 };
+
+        let mut header = Vec::with_capacity(30 + name.len() + extra.len());
+        put_u32(&mut header, LOCAL_HEADER_SIGNATURE);
+        put_u16(&mut header, if large { 45 } else { 20 });
+        put_u16(&mut header, FLAGS);
+        put_u16(&mut header, METHOD_DEFLATED);
+        put_u16(&mut header, time);
+        put_u16(&mut header, date);
+        put_u32(&mut header, 0); // crc, in the data descriptor
+        put_u32(&mut header, placeholder);
+        put_u32(&mut header, placeholder);
+        put_u16(&mut header, name.len() as u16);
+        put_u16(&mut header, extra.len() as u16);
+        header.extend_from_slice(name.as_bytes());
+        header.extend_from_slice(&extra);
+
+        let header_offset = self.offset;
+        self.write_raw(&header)?;
+        self.compress.reset();
+        self.current = Some(Entry {
+            name: name.to_string(),
+            raw_name,
+            large,
+            header_offset,
+            data_offset: self.offset,
+            time,
+            date,
+            crc: Crc::new(),
+            size: 0,
+        });
+        Ok(())
+    }
+
+    fn abort_entry(&mut self) -> Result<(), BackupError> {
+        // The bytes already written cannot be taken back; leaving the entry out of the
+        // central directory hides them from every reader
+        self.current = None;
+        Ok(())
+    }
+
+    fn finish_archive(mut self) -> Result<W, BackupError> {
+        self.finish_entry()?;
+
+        let central_offset = self.offset;
+        for index in 0..self.entries.len() {
+            let header = Self::central_header(&self.entries[index]);
+            self.write_raw(&header)?;
+        }
+        let central_size = self.offset - central_offset;
+        let count = self.entries.len() as u64;
+
+        let mut end = Vec::new();
+        if count >= u16::MAX as u64
+            || central_offset >= ZIP64_THRESHOLD
+            || central_size >= ZIP64_THRESHOLD
+        {
+            let zip64_end_offset = self.offset;
+            put_u32(&mut end, ZIP64_END_SIGNATURE);
+            put_u64(&mut end, 44); // size of the rest of the record
+            put_u16(&mut end, VERSION_MADE_BY);
+            put_u16(&mut end, 45);
+            put_u32(&mut end, 0); // this disk
+            put_u32(&mut end, 0); // disk with the central directory
+            put_u64(&mut end, count);
+            put_u64(&mut end, count);
+            put_u64(&mut end, central_size);
+            put_u64(&mut end, central_offset);
+
+            put_u32(&mut end, ZIP64_LOCATOR_SIGNATURE);
+            put_u32(&mut end, 0);
+            put_u64(&mut end, zip64_end_offset);
+            put_u32(&mut end, 1); // total disks
+        }
+        put_u32(&mut end, END_SIGNATURE);
+        put_u16(&mut end, 0);
+        put_u16(&mut end, 0);
+        put_u16(&mut end, count.min(u16::MAX as u64) as u16);
+        put_u16(&mut end, count.min(u16::MAX as u64) as u16);
+        put_u32(&mut end, central_size.min(ZIP64_THRESHOLD) as u32);
+        put_u32(&mut end, central_offset.min(ZIP64_THRESHOLD) as u32);
+        put_u16(&mut end, 0); // comment length
+        self.write_raw(&end)?;
+
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_extra_field(out: &mut Vec<u8>, id: u16, data: &[u8]) {
+    put_u16(out, id);
+    put_u16(out, data.len() as u16);
+    out.extend_from_slice(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::rawpath::entry_indices;
+    use std::io::{Cursor, Read};
+    use std::path::PathBuf;
+    use zip::ZipArchive;
+
+    fn read_entry(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn streamed_archive_reads_back_without_aborted_entries() {
+        let small = b"small entry".to_vec();
+        let large: Vec<u8> = (0..200_000u32).flat_map(|i| i.to_le_bytes()).collect();
+
+        // A Vec cannot seek, so this also checks the writer never needs to
+        let mut zip = StreamingZipWriter::new(Vec::new());
+        zip.start_entry("small.txt", None, false).unwrap();
+        zip.write_all(&small).unwrap();
+        zip.start_entry("aborted.txt", None, false).unwrap();
+        zip.write_all(b"never listed").unwrap();
+        zip.abort_entry().unwrap();
+        zip.start_entry("large.bin", None, true).unwrap();
+        zip.write_all(&large).unwrap();
+        zip.start_entry("escaped.txt", Some(b"raw.txt".to_vec()), false)
+            .unwrap();
+        zip.write_all(b"raw").unwrap();
+        let bytes = zip.finish_archive().unwrap();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.len(), 3);
+        assert!(archive.by_name("aborted.txt").is_err());
+        assert_eq!(read_entry(&mut archive, "small.txt"), small);
+        assert_eq!(read_entry(&mut archive, "large.bin"), large);
+        assert_eq!(read_entry(&mut archive, "escaped.txt"), b"raw");
+
+        let indices = entry_indices(&mut archive).unwrap();
+        assert!(indices.contains_key(&PathBuf::from("raw.txt")));
+    }
+}
@@ -203,6 +203,9 @@ pub struct DriveFile {
 pub struct DriveFileList {
     pub files: Vec<DriveFile>,
     pub next_page_token: Option<String>,
+    /// Set when Drive could not search everything, so `files` may be missing some
+    #[serde(default)]
+    pub incomplete_search: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -222,6 +225,21 @@ pub enum UploadStatus {
     Cancelled,
 }
 
+/// An open resumable upload whose total length is only known when the last chunk is sent.
+#[derive(Debug, Clone)]
+pub struct UploadSession {
+    pub file_name: String,
+    upload_url: String,
+    offset: u64,
+}
+
+impl UploadSession {
+    /// Bytes Drive has confirmed so far
+    pub fn bytes_uploaded(&self) -> u64 {
+        self.offset
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DriveConfig {
     pub client_id: String,
@@ -688,6 +706,187 @@ impl GoogleDriveClient {
         Ok(uploaded_file)
     }
 
+    /// Open a resumable upload session in the backup folder without declaring its length,
+    /// so the content can be produced while it is being uploaded.
+    pub async fn start_upload_session(
+        &mut self,
+        file_name: &str,
+    ) -> Result<UploadSession, DriveError> {
+        let folder_id = self.get_or_create_backup_folder().await?;
+        let file_metadata = serde_json::json!({
+            "name": file_name,
+            "parents": [folder_id]
+        });
+
+        // Two attempts max: initial + one retry after refresh
+        for attempt in 0..=1 {
+            let access_token = self.ensure_authenticated().await?;
+
+            let response = self
+                .client
+                .post(format!("{}/files?uploadType=resumable", Self::UPLOAD_BASE))
+                .bearer_auth(&access_token)
+                .header("Content-Type", "application/json")
+                .header("X-Upload-Content-Type", "application/octet-stream")
+                .json(&file_metadata)
+                .send()
+                .await?;
+
+            if (response.status().as_u16() == 401 || response.status().as_u16() == 403)
+                && attempt == 0
+            {
+                self.refresh_token().await?;
+                continue;
+            }
+
+            if !response.status().is_success() {
+                return Err(DriveError::UploadFailed(response.text().await?));
+            }
+
+            let upload_url = response
+                .headers()
+                .get("location")
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| DriveError::UploadFailed("No upload URL received".to_string()))?
+                .to_string();
+
+            return Ok(UploadSession {
+                file_name: file_name.to_string(),
+                upload_url,
+                offset: 0,
+            });
+        }
+
+        Err(DriveError::UploadFailed(
+            "Could not start upload session after retry".to_string(),
+        ))
+    }
+
+    /// Send the next chunk of a session. Every chunk except the last must be a multiple of
+    /// 256 KiB; pass `total_size` with the last chunk to complete the upload and get the file.
+    /// Transient failures are retried from the offset Drive reports as stored.
+    pub async fn upload_session_chunk(
+        &mut self,
+        session: &mut UploadSession,
+        data: &[u8],
+        total_size: Option<u64>,
+    ) -> Result<Option<DriveFile>, DriveError> {
+        const MAX_ATTEMPTS: u32 = 3;
+        let start = session.offset;
+        let end = start + data.len() as u64;
+        let total = total_size.map_or_else(|| "*".to_string(), |t| t.to_string());
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            let body = data[(session.offset.min(end) - start) as usize..].to_vec();
+            let content_range = if body.is_empty() {
+                if total_size.is_none() {
+                    return Ok(None);
+                }
+                format!("bytes */{}", total)
+            } else {
+                format!("bytes {}-{}/{}", session.offset, end - 1, total)
+            };
+
+            let access_token = self.ensure_authenticated().await?;
+            let response = self
+                .client
+                .put(&session.upload_url)
+                .bearer_auth(&access_token)
+                .header("Content-Range", content_range)
+                .header("Content-Length", body.len().to_string())
+                .body(body)
+                .send()
+                .await;
+
+            match response {
+                Ok(response) if response.status().is_success() => {
+                    session.offset = end;
+                    return Ok(Some(response.json().await?));
+                }
+                Ok(response) if response.status().as_u16() == 308 => {
+                    session.offset = Self::resume_offset(Self::confirmed_offset(&response), start)?;
+                    if session.offset >= end && total_size.is_none() {
+                        return Ok(None);
+                    }
+                    // Only part of the chunk was stored; send the rest
+                    continue;
+                }
+                Ok(response)
+                    if response.status().is_server_error()
+                        || response.status().as_u16() == 429 =>
+                {
+                    if attempt == MAX_ATTEMPTS {
+                        return Err(DriveError::UploadFailed(response.text().await?));
+                    }
+                }
+                Ok(response) => return Err(DriveError::UploadFailed(response.text().await?)),
+                Err(e) if attempt == MAX_ATTEMPTS => return Err(e.into()),
+                Err(_) => {}
+            }
+
+            tokio::time::sleep(std::time::Duration::from_secs(1 << attempt)).await;
+            session.offset = Self::resume_offset(self.query_session_offset(session).await?, start)?;
+        }
+
+        Err(DriveError::UploadFailed(format!(
+            "Chunk at offset {} of {} failed after {} attempts",
+            start, session.file_name, MAX_ATTEMPTS
+        )))
+    }
+
+    /// Ask Drive how many bytes of the session it has stored.
+    async fn query_session_offset(&mut self, session: &UploadSession) -> Result<u64, DriveError> {
+        let access_token = self.ensure_authenticated().await?;
+        let response = self
+            .client
+            .put(&session.upload_url)
+            .bearer_auth(&access_token)
+            .header("Content-Range", "bytes */*")
+            .header("Content-Length", "0")
+            .send()
+            .await?;
+
+        if response.status().as_u16() == 308 {
+            Ok(Self::confirmed_offset(&response))
+        } else {
+            Err(DriveError::UploadFailed(response.text().await?))
+        }
+    }
+
+    /// Parse the `Range: bytes=0-N` header of a 308 response into the next byte offset.
+    fn confirmed_offset(response: &reqwest::Response) -> u64 {
+        response
+            .headers()
+            .get("range")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit('-').next())
+            .and_then(|last| last.parse::<u64>().ok())
+            .map_or(0, |last| last + 1)
+    }
+
+    /// Check that Drive still holds every byte before the current chunk. Earlier chunks are
+    /// gone from the spool, so an offset below `start` cannot be resumed.
+    fn resume_offset(offset: u64, start: u64) -> Result<u64, DriveError> {
+        if offset < start {
+            return Err(DriveError::UploadFailed(format!(
+                "Drive confirmed {} bytes, but {} had already been sent",
+                offset, start
+            )));
+        }
+        Ok(offset)
+    }
+
+    /// Abandon a session so Drive discards the partial upload.
+    pub async fn cancel_upload_session(&mut self, session: UploadSession) -> Result<(), DriveError> {
+        let access_token = self.ensure_authenticated().await?;
+        self.client
+            .delete(&session.upload_url)
+            .bearer_auth(&access_token)
+            .send()
+            .await?;
+        Ok(())
+    }
+
     async fn build_progress_stream(
         file_path: &Path,
         file_name: &str,
@@ -789,31 +988,54 @@ impl GoogleDriveClient {
         Ok(content.to_vec())
     }
 
+    /// Every file in the backup folder, newest first, following `nextPageToken` until the
+    /// listing is complete.
     pub async fn list_backups(&mut self) -> Result<Vec<DriveFile>, DriveError> {
         let access_token = self.ensure_authenticated().await?;
         let folder_id = self.get_or_create_backup_folder().await?;
 
         let query = format!("'{}' in parents and trashed=false", folder_id);
+        let mut files = Vec::new();
+        let mut page_token: Option<String> = None;
 
-        let response = self
-            .client
-            .get(format!("{}/files", Self::API_BASE))
-            .bearer_auth(&access_token)
-            .query(&[
+        loop {
+            let mut params = vec![
                 ("q", query.as_str()),
                 (
                     "fields",
-                    "files(id,name,size,createdTime,modifiedTime,webViewLink)",
+                    "nextPageToken,incompleteSearch,files(id,name,size,createdTime,modifiedTime,webViewLink)",
                 ),
                 ("orderBy", "createdTime desc"),
-            ])
-            .send()
-            .await?;
+                ("pageSize", "1000"),
+            ];
+            if let Some(token) = page_token.as_deref() {
+                params.push(("pageToken", token));
+            }
 
-        let file_list: DriveFileList = Self::parse_json_response(response).await?;
-        Ok(file_list.files)
+            let response = self
+                .client
+                .get(format!("{}/files", Self::API_BASE))
+                .bearer_auth(&access_token)
+                .query(&params)
+                .send()
+                .await?;
+
+            let file_list: DriveFileList = Self::parse_json_response(response).await?;
+            // Callers delete and recover from this listing, so a partial one is an error
+            if file_list.incomplete_search {
+                return Err(DriveError::Api(
+                    "Drive could not list the whole backup folder; try again later".to_string(),
+                ));
+            }
+            files.extend(file_list.files);
+            match file_list.next_page_token {
+                Some(token) => page_token = Some(token),
+                None => return Ok(files),
+            }
+        }
     }
 
+    /// Delete a file; a file that no longer exists is reported as `FileNotFound`.
     pub async fn delete_file(&mut self, file_id: &str) -> Result<(), DriveError> {
         let access_token = self.ensure_authenticated().await?;
 
@@ -824,6 +1046,9 @@ impl GoogleDriveClient {
             .send()
             .await?;
 
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(DriveError::FileNotFound(file_id.to_string()));
+        }
         if !response.status().is_success() {
             let error_text = response.text().await?;
             return Err(DriveError::Api(error_text));
@@ -832,6 +1057,21 @@ impl GoogleDriveClient {
         Ok(())
     }
 
+    /// Rename a file in place, keeping its id and content.
+    pub async fn rename_file(&mut self, file_id: &str, name: &str) -> Result<DriveFile, DriveError> {
+        let access_token = self.ensure_authenticated().await?;
+
+        let response = self
+            .client
+            .patch(format!("{}/files/{}", Self::API_BASE, file_id))
+            .bearer_auth(&access_token)
+            .json(&serde_json::json!({ "name": name }))
+            .send()
+            .await?;
+
+        Self::parse_json_response(response).await
+    }
+
     pub async fn get_storage_quota(&mut self) -> Result<(u64, u64), DriveError> {
         let access_token = self.ensure_authenticated().await?;
 
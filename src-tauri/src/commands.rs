@@ -1,34 +1,63 @@
 //! Tauri Commands - Bridge between frontend and backend
 #![allow(non_snake_case)]
 
-use crate::backup::engine::{BackupEngine, BackupResult};
-use crate::backup::manifest::{BackupManifest, ManifestSummary};
+use crate::backup::descriptor::{self, ArchiveVerification};
+use crate::backup::diff::{self, ManifestDiff};
+use crate::backup::engine::{BackupEngine, BackupResult, RestoreOptions, RestoreResult};
+use crate::backup::estimate::SizeEstimate;
+use crate::backup::history::{BackupRun, BackupRunPage, RunHistory, RunUploadStatus};
+use crate::backup::manifest::{
+    BackupManifest, CloudChunk, CloudLocation, IndexRebuild, ManifestDetail, ManifestManager,
+    ManifestSummary, VersionInfo,
+};
+use crate::backup::portable::{ConflictPolicy, DefinitionsFile, ImportReport};
+use crate::backup::protection::ProtectionStatus;
+use crate::backup::rawpath::decode_path;
+use crate::backup::recovery::{
+    recover_manifests, scan_archive_folder, FoundBackups, RecoveryFailure, RecoveryReport,
+};
+use crate::backup::retention::{
+    plan_retention, prune_local, PruneCandidate, PrunedVersion, RetentionPlan,
+};
+use crate::backup::search::{search_files, FileSearchQuery, FileSearchResult};
 use crate::backup::scheduler::{Schedule, ScheduleType, WeatherAlertType, WeatherTrigger};
-use crate::backup::set::{BackupPreset, BackupSet};
-use crate::cloud::google_drive::{DriveConfig, DriveFile, GoogleDriveClient};
+use crate::backup::set::{BackupMode, BackupPreset, BackupSet, GfsPolicy};
+use crate::backup::spool::{ChunkSink, StreamedArchive};
+use crate::backup::validate::{self, ValidationReport};
+use crate::cloud::google_drive::{
+    DriveConfig, DriveError, DriveFile, GoogleDriveClient, UploadProgress, UploadSession,
+    UploadStatus,
+};
 use crate::state::{AppSettings, AppState, OnboardingState, StateManager};
 use crate::weather::{Location, WeatherAlert, WeatherConditions, WeatherService};
 
+use chrono::Utc;
 use futures_util::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::{self, Value};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter, Manager, State};
 use tokio::sync::Mutex;
 use urlencoding::decode;
+use uuid::Uuid;
 
 // State wrappers for thread-safe access
 pub struct AppStateManager(pub Arc<Mutex<StateManager>>);
 pub struct BackupEngineState(pub Arc<Mutex<Option<BackupEngine>>>);
 pub struct DriveClientState(pub Arc<Mutex<Option<GoogleDriveClient>>>);
 pub struct WeatherServiceState(pub Arc<Mutex<WeatherService>>);
+/// Shared with the backup engine; manifests are read without waiting on a running backup
+pub struct ManifestManagerState(pub Arc<ManifestManager>);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CloudBackupBundle {
     pub manifest: BackupManifest,
     pub manifest_file: DriveFile,
     pub archive_file: DriveFile,
+    /// Drive files of a multi-volume archive in order; empty when uploaded in one piece
+    pub volumes: Vec<DriveFile>,
 }
 
 // Response types
@@ -55,21 +84,83 @@ impl<T> CommandResult<T> {
             error: Some(error),
         }
     }
+
+    /// A failure that still carries details, e.g. the validation report that rejected a save.
+    pub fn err_with(error: String, data: T) -> Self {
+        Self {
+            success: false,
+            data: Some(data),
+            error: Some(error),
+        }
+    }
+}
+
+/// A backup set with the validation report of its save; the set is only stored when the
+/// report has no errors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedBackupSet {
+    pub set: BackupSet,
+    pub validation: ValidationReport,
 }
 
 /// Shared executor used by manual and scheduled backups to keep progress payloads consistent.
+/// `mode` overrides the set's configured backup mode when provided.
+/// Every call leaves a `BackupRun` in the run history, whether it succeeds or not.
 pub async fn execute_backup_with_trigger(
     backup_set_id: String,
-    incremental: bool,
+    mode: Option<BackupMode>,
+    trigger: &str,
+    app: AppHandle,
+    state: Arc<Mutex<StateManager>>,
+    engine_state: Arc<Mutex<Option<BackupEngine>>>,
+    drive_state: Arc<Mutex<Option<GoogleDriveClient>>>,
+) -> Result<BackupResult, String> {
+    let history = app.path().app_data_dir().ok().map(RunHistory::new);
+    let mut run = BackupRun::start(&backup_set_id, trigger, mode.unwrap_or_default());
+    save_run(history.as_ref(), &run);
+
+    let result = run_backup_job(
+        backup_set_id,
+        mode,
+        trigger,
+        app,
+        state,
+        engine_state,
+        drive_state,
+        &mut run,
+    )
+    .await;
+
+    run.finish(result.as_ref().err().cloned());
+    save_run(history.as_ref(), &run);
+    result
+}
+
+fn save_run(history: Option<&RunHistory>, run: &BackupRun) {
+    match history {
+        Some(history) => {
+            if let Err(e) = history.save_run(run) {
+                eprintln!("Failed to record backup run {}: {}", run.id, e);
+            }
+        }
+        None => eprintln!("Backup run {} not recorded: no app data directory", run.id),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_backup_job(
+    backup_set_id: String,
+    mode: Option<BackupMode>,
     trigger: &str,
     app: AppHandle,
     state: Arc<Mutex<StateManager>>,
     engine_state: Arc<Mutex<Option<BackupEngine>>>,
     drive_state: Arc<Mutex<Option<GoogleDriveClient>>>,
+    run: &mut BackupRun,
 ) -> Result<BackupResult, String> {
     println!(
-        "execute_backup_with_trigger: backup_set_id={}, incremental={}, trigger={}",
-        backup_set_id, incremental, trigger
+        "execute_backup_with_trigger: backup_set_id={}, mode={:?}, trigger={}",
+        backup_set_id, mode, trigger
     );
 
     let manager = state.lock().await;
@@ -78,12 +169,15 @@ pub async fn execute_backup_with_trigger(
         .backup_sets
         .get_set(&backup_set_id)
         .cloned();
+    let chunk_size_mb = manager.get_state().settings.chunk_size_mb;
+    let stream_uploads = manager.get_state().settings.stream_uploads;
     drop(manager);
 
     let Some(backup_set) = backup_set else {
         println!("Backup set not found: {}", backup_set_id);
         return Err("Backup set not found".to_string());
     };
+    run.backup_set_name = Some(backup_set.name.clone());
 
     println!(
         "Found backup set: {} with {} sources",
@@ -91,6 +185,12 @@ pub async fn execute_backup_with_trigger(
         backup_set.sources.len()
     );
 
+    // Sets saved before validation existed may still point their destination into a source
+    if let Some(message) = validate::validate_backup_set(&backup_set).error_message() {
+        eprintln!("Backup set {} is invalid: {}", backup_set.name, message);
+        return Err(message);
+    }
+
     let mut engine_guard = engine_state.lock().await;
     let engine = engine_guard
         .as_mut()
@@ -102,7 +202,9 @@ pub async fn execute_backup_with_trigger(
     let backup_set_id_for_progress = backup_set_id.clone();
     let trigger_label = trigger.to_string();
     let trigger_label_for_progress = trigger_label.clone();
-    let result = engine.execute_backup(&backup_set, incremental, move |progress| {
+    let mode = mode.unwrap_or(backup_set.mode);
+    run.mode = mode;
+    let progress_callback = move |progress| {
         let mut value: Value = serde_json::to_value(&progress).unwrap_or(Value::Null);
         if let Value::Object(ref mut map) = value {
             map.insert(
@@ -115,91 +217,125 @@ pub async fn execute_backup_with_trigger(
             );
         }
         let _ = progress_handle.emit("backup:progress", value);
-    });
+    };
+
+    // Cloud-only sets can stream straight into Drive so the full archive never lands in temp.
+    // Drive is locked after the engine, the same order retention uses, and both are held
+    // until the last chunk is sent
+    let mut stream_guard = if stream_uploads
+        && backup_set.cloud_upload
+        && backup_set.local_destination.is_none()
+    {
+        Some(drive_state.lock().await)
+    } else {
+        None
+    };
+    let mut streamed_file = None;
+    let result = match stream_guard.as_mut().and_then(|guard| guard.as_mut()) {
+        Some(client) => {
+            println!("Streaming archive to Google Drive");
+            engine.set_chunk_size_mb(chunk_size_mb);
+            let mut sink = DriveUploadSink::new(client, &app, &backup_set.id);
+            let result =
+                engine.execute_streamed_backup(&backup_set, mode, &mut sink, progress_callback);
+            streamed_file = sink.finish().await;
+            result
+        }
+        None => engine.execute_backup(&backup_set, mode, progress_callback),
+    };
+    drop(stream_guard);
     drop(engine_guard);
 
     match result {
         Ok(result) => {
-            let no_changes = result.total_bytes == 0 && result.total_files == 0;
+            run.record_result(&result);
+            let no_changes = result.is_unchanged();
 
             if !no_changes {
                 // Handle cloud upload if enabled
                 if backup_set.cloud_upload {
+                    // Volumes are cut before Drive is locked: the engine is never awaited while
+                    // Drive is held, as streaming and retention lock the engine first
+                    let volumes = match &result.streamed {
+                        None if result.archive_path.exists()
+                            && drive_state.lock().await.is_some() =>
+                        {
+                            Some(
+                                split_for_upload(&engine_state, &result.archive_path, chunk_size_mb)
+                                    .await,
+                            )
+                        }
+                        _ => None,
+                    };
+                    let manifest_manager = app.state::<ManifestManagerState>().0.clone();
                     let mut client_guard = drive_state.lock().await;
 
                     if let Some(client) = client_guard.as_mut() {
-                        if !result.archive_path.exists() {
-                            let msg = format!(
+                        let uploaded = match (&result.streamed, streamed_file, volumes) {
+                            (Some(streamed), Some(file), _) => {
+                                record_streamed_archive(
+                                    client,
+                                    &manifest_manager,
+                                    &result,
+                                    streamed,
+                                    file,
+                                )
+                                .await
+                            }
+                            (Some(_), None, _) => {
+                                Err("Streamed upload did not complete".to_string())
+                            }
+                            (None, _, Some(Ok(volumes))) => {
+                                upload_archive_volumes(
+                                    client,
+                                    &app,
+                                    &manifest_manager,
+                                    &result,
+                                    volumes,
+                                )
+                                .await
+                            }
+                            (None, _, Some(Err(e))) => Err(e),
+                            (None, _, None) => Err(format!(
                                 "Archive path missing for upload: {:?}",
                                 result.archive_path
-                            );
-                            eprintln!("{msg}");
-                            let _ = app.emit("upload:error", msg);
-                        } else {
-                            let archive_name = format!("backup_{}.zip", result.id);
-                            let progress_handle = app.clone();
-                            let error_handle = app.clone();
-                            match client
-                                .upload_file(&result.archive_path, &archive_name, move |progress| {
-                                    let _ = progress_handle.emit("upload:progress", progress);
-                                })
-                                .await
-                            {
-                                Ok(_drive_file) => {
-                                    println!("Archive uploaded successfully");
-
-                                    // Upload Manifest
-                                    if let Ok(app_data_dir) = app.path().app_data_dir() {
-                                        let manifest_path = app_data_dir
-                                            .join("manifests")
-                                            .join(result.manifest_file_name());
-                                        let manifest_name =
-                                            format!("manifest_{}.json", result.id.clone());
-                                        if manifest_path.exists() {
-                                            let progress_handle = app.clone();
-                                            match client
-                                                .upload_file(
-                                                    &manifest_path,
-                                                    &manifest_name,
-                                                    move |progress| {
-                                                        let _ = progress_handle
-                                                            .emit("upload:progress", progress);
-                                                    },
-                                                )
-                                                .await
-                                            {
-                                                Ok(_manifest_file) => {
-                                                    println!("Manifest uploaded successfully");
-                                                }
-                                                Err(e) => {
-                                                    let msg = format!(
-                                                        "Manifest upload failed: {}",
-                                                        e
-                                                    );
-                                                    eprintln!("{msg}");
-                                                    let _ = error_handle.emit("upload:error", msg);
-                                                }
-                                            }
-                                        } else {
-                                            let msg = format!(
-                                                "Manifest file not found at {:?}",
-                                                manifest_path
-                                            );
-                                            eprintln!("{msg}");
-                                            let _ = error_handle.emit("upload:error", msg);
-                                        }
+                            )),
+                        };
+
+                        match uploaded {
+                            Ok(location) => {
+                                println!(
+                                    "Archive uploaded successfully in {} volume(s)",
+                                    location.chunks.len()
+                                );
+                                run.record_upload(
+                                    RunUploadStatus::Uploaded,
+                                    location.chunks.len() as u32,
+                                    None,
+                                );
+                                match upload_manifest(client, &app, &result).await {
+                                    Ok(()) => println!("Manifest uploaded successfully"),
+                                    Err(msg) => {
+                                        eprintln!("{msg}");
+                                        run.errors.push(msg.clone());
+                                        let _ = app.emit("upload:error", msg);
                                     }
                                 }
-                                Err(e) => {
-                                    let msg = format!("Cloud upload failed: {}", e);
-                                    eprintln!("{msg}");
-                                    let _ = app.emit("upload:error", msg);
-                                }
+                            }
+                            Err(e) => {
+                                let msg = format!("Cloud upload failed: {}", e);
+                                eprintln!("{msg}");
+                                run.record_upload(RunUploadStatus::Failed, 0, Some(msg.clone()));
+                                let _ = app.emit("upload:error", msg);
                             }
                         }
                     } else {
+                        if let Some(Ok(volumes)) = &volumes {
+                            remove_split_volumes(volumes);
+                        }
                         let msg = "Cloud upload skipped: Google Drive not connected".to_string();
                         eprintln!("{msg}");
+                        run.record_upload(RunUploadStatus::Skipped, 0, Some(msg.clone()));
                         let _ = app.emit("upload:error", msg);
                     }
                 }
@@ -219,6 +355,12 @@ pub async fn execute_backup_with_trigger(
                     set.record_backup(result.total_bytes);
                 }
                 manager.save().ok();
+                drop(manager);
+
+                if let Err(e) = enforce_retention(&backup_set, &engine_state, &drive_state).await {
+                    eprintln!("Retention failed for {}: {}", backup_set.name, e);
+                    run.errors.push(format!("Retention failed: {}", e));
+                }
             } else {
                 println!(
                     "Backup skipped: no changes detected for {} (trigger: {})",
@@ -232,6 +374,378 @@ pub async fn execute_backup_with_trigger(
     }
 }
 
+/// Upload the manifest of a finished backup next to its archive.
+async fn upload_manifest(
+    client: &mut GoogleDriveClient,
+    app: &AppHandle,
+    result: &BackupResult,
+) -> Result<(), String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let manifest_path = app_data_dir
+        .join("manifests")
+        .join(result.manifest_file_name());
+    if !manifest_path.exists() {
+        return Err(format!("Manifest file not found at {:?}", manifest_path));
+    }
+
+    let manifest_name = format!("manifest_{}.json", result.id);
+    let progress_handle = app.clone();
+    client
+        .upload_file(&manifest_path, &manifest_name, move |progress| {
+            let _ = progress_handle.emit("upload:progress", progress);
+        })
+        .await
+        .map_err(|e| format!("Manifest upload failed: {}", e))?;
+
+    Ok(())
+}
+
+/// Feeds a streamed archive into a Drive resumable upload session, opened on the first chunk.
+/// The engine writes synchronously, so each chunk is sent by blocking on the async client.
+/// `block_in_place` needs the multi-threaded runtime Tauri starts commands on; it panics on a
+/// current-thread runtime. The engine and Drive locks stay held while it blocks, so for the
+/// whole streamed run other backups, pins, retention and restores wait on them.
+struct DriveUploadSink<'a> {
+    client: &'a mut GoogleDriveClient,
+    app: AppHandle,
+    file_name: String,
+    session: Option<UploadSession>,
+    file: Option<DriveFile>,
+}
+
+impl<'a> DriveUploadSink<'a> {
+    fn new(client: &'a mut GoogleDriveClient, app: &AppHandle, backup_set_id: &str) -> Self {
+        Self {
+            client,
+            app: app.clone(),
+            // Renamed to the usual archive name once the manifest id is known
+            file_name: format!("backup_{}_{}.zip.partial", backup_set_id, Uuid::new_v4()),
+            session: None,
+            file: None,
+        }
+    }
+
+    async fn send(&mut self, data: &[u8], total_size: Option<u64>) -> Result<(), DriveError> {
+        if self.session.is_none() {
+            self.session = Some(self.client.start_upload_session(&self.file_name).await?);
+        }
+        let Some(session) = self.session.as_mut() else {
+            return Ok(());
+        };
+
+        if let Some(file) = self
+            .client
+            .upload_session_chunk(session, data, total_size)
+            .await?
+        {
+            self.file = Some(file);
+        }
+
+        let uploaded = session.bytes_uploaded();
+        let _ = self.app.emit(
+            "upload:progress",
+            UploadProgress {
+                bytes_uploaded: uploaded,
+                total_bytes: total_size.unwrap_or(uploaded),
+                file_name: self.file_name.clone(),
+                status: if self.file.is_some() {
+                    UploadStatus::Completed
+                } else {
+                    UploadStatus::Uploading
+                },
+            },
+        );
+        Ok(())
+    }
+
+    /// Return the uploaded file, or discard the session if the archive never completed.
+    async fn finish(self) -> Option<DriveFile> {
+        if self.file.is_none() {
+            if let Some(session) = self.session {
+                let _ = self.client.cancel_upload_session(session).await;
+            }
+        }
+        self.file
+    }
+}
+
+impl ChunkSink for DriveUploadSink<'_> {
+    fn write_chunk(&mut self, data: &[u8], total_size: Option<u64>) -> std::io::Result<()> {
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(self.send(data, total_size))
+        })
+        .map_err(|e| std::io::Error::other(e.to_string()))
+    }
+}
+
+/// Name a streamed archive after its manifest and record it as the manifest's cloud location.
+async fn record_streamed_archive(
+    client: &mut GoogleDriveClient,
+    manifest_manager: &ManifestManager,
+    result: &BackupResult,
+    streamed: &StreamedArchive,
+    file: DriveFile,
+) -> Result<CloudLocation, String> {
+    let archive_name = archive_volume_name(&result.id, 0, false);
+    if let Err(e) = client.rename_file(&file.id, &archive_name).await {
+        eprintln!("Failed to rename streamed archive to {}: {}", archive_name, e);
+    }
+
+    let folder_id = client
+        .get_or_create_backup_folder()
+        .await
+        .map_err(|e| e.to_string())?;
+    let location = CloudLocation {
+        provider: "google_drive".to_string(),
+        file_id: file.id.clone(),
+        folder_id,
+        chunks: vec![CloudChunk {
+            index: 0,
+            file_id: file.id,
+            size: streamed.size,
+            hash: streamed.hash.clone(),
+        }],
+    };
+
+    manifest_manager
+        .update_cloud_location(&result.id, location.clone())
+        .map_err(|e| e.to_string())?;
+
+    Ok(location)
+}
+
+/// Drive name of an archive volume; archives uploaded in one piece keep the plain archive name.
+fn archive_volume_name(manifest_id: &str, index: usize, split: bool) -> String {
+    if split {
+        format!("backup_{}.zip.part{:03}", manifest_id, index)
+    } else {
+        format!("backup_{}.zip", manifest_id)
+    }
+}
+
+/// Cut an archive into upload volumes of `chunk_size_mb`.
+async fn split_for_upload(
+    engine_state: &Arc<Mutex<Option<BackupEngine>>>,
+    archive_path: &Path,
+    chunk_size_mb: u32,
+) -> Result<Vec<PathBuf>, String> {
+    let mut engine_guard = engine_state.lock().await;
+    let engine = engine_guard
+        .as_mut()
+        .ok_or("Backup engine not initialized")?;
+    engine.set_chunk_size_mb(chunk_size_mb);
+    engine
+        .split_into_chunks(archive_path)
+        .map_err(|e| e.to_string())
+}
+
+/// Remove the volume files of a split archive; a single volume is the archive itself.
+fn remove_split_volumes(volumes: &[PathBuf]) {
+    if volumes.len() > 1 {
+        for volume in volumes {
+            let _ = std::fs::remove_file(volume);
+        }
+    }
+}
+
+/// Upload an archive as fixed-size volumes and record their Drive ids and hashes on its manifest.
+async fn upload_archive_volumes(
+    client: &mut GoogleDriveClient,
+    app: &AppHandle,
+    manifest_manager: &ManifestManager,
+    result: &BackupResult,
+    volumes: Vec<PathBuf>,
+) -> Result<CloudLocation, String> {
+    let split = volumes.len() > 1;
+    let uploaded = upload_volumes(client, app, &result.id, &volumes, split).await;
+    remove_split_volumes(&volumes);
+    let chunks = uploaded?;
+
+    let folder_id = client
+        .get_or_create_backup_folder()
+        .await
+        .map_err(|e| e.to_string())?;
+    let location = CloudLocation {
+        provider: "google_drive".to_string(),
+        file_id: chunks[0].file_id.clone(),
+        folder_id,
+        chunks,
+    };
+
+    manifest_manager
+        .update_cloud_location(&result.id, location.clone())
+        .map_err(|e| e.to_string())?;
+
+    Ok(location)
+}
+
+/// Upload each volume in order; volumes already uploaded are removed again if a later one fails.
+async fn upload_volumes(
+    client: &mut GoogleDriveClient,
+    app: &AppHandle,
+    manifest_id: &str,
+    volumes: &[PathBuf],
+    split: bool,
+) -> Result<Vec<CloudChunk>, String> {
+    let mut chunks: Vec<CloudChunk> = Vec::with_capacity(volumes.len());
+
+    for (index, volume) in volumes.iter().enumerate() {
+        let name = archive_volume_name(manifest_id, index, split);
+        let uploaded = async {
+            let hash = BackupEngine::calculate_hash(volume).map_err(|e| e.to_string())?;
+            let size = std::fs::metadata(volume).map_err(|e| e.to_string())?.len();
+            let progress_handle = app.clone();
+            let file = client
+                .upload_file(volume, &name, move |progress| {
+                    let _ = progress_handle.emit("upload:progress", progress);
+                })
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok::<CloudChunk, String>(CloudChunk {
+                index: index as u32,
+                file_id: file.id,
+                size,
+                hash,
+            })
+        }
+        .await;
+
+        match uploaded {
+            Ok(chunk) => chunks.push(chunk),
+            Err(e) => {
+                for chunk in &chunks {
+                    let _ = client.delete_file(&chunk.file_id).await;
+                }
+                return Err(format!("Volume {} ({}) failed: {}", index, name, e));
+            }
+        }
+    }
+
+    Ok(chunks)
+}
+
+/// Prune expired versions of a set from Drive first, then from the local destination and index.
+/// Versions whose cloud copies cannot be removed are kept so the catalog stays consistent.
+/// The engine stays locked from planning to the last deletion, so no version can be pinned
+/// in between; Drive is locked inside it.
+pub async fn enforce_retention(
+    backup_set: &BackupSet,
+    engine_state: &Arc<Mutex<Option<BackupEngine>>>,
+    drive_state: &Arc<Mutex<Option<GoogleDriveClient>>>,
+) -> Result<Vec<PrunedVersion>, String> {
+    let engine_guard = engine_state.lock().await;
+    let engine = engine_guard
+        .as_ref()
+        .ok_or("Backup engine not initialized")?;
+    let manifest_manager = engine.manifest_manager();
+
+    let plan =
+        plan_retention(backup_set, manifest_manager, Utc::now()).map_err(|e| e.to_string())?;
+
+    let mut versions = Vec::with_capacity(plan.prune.len());
+    for candidate in &plan.prune {
+        match manifest_manager
+            .load_manifest_by_id(&candidate.manifest.id)
+            .map_err(|e| e.to_string())?
+        {
+            Some(manifest) if !manifest.pinned => versions.push(manifest),
+            _ => {}
+        }
+    }
+    let candidates: Vec<PruneCandidate> = plan
+        .prune
+        .into_iter()
+        .filter(|c| versions.iter().any(|m| m.id == c.manifest.id))
+        .collect();
+    if candidates.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut client_guard = drive_state.lock().await;
+    let candidates = match client_guard.as_mut() {
+        Some(client) => {
+            let failed = delete_drive_versions(client, &versions)
+                .await
+                .map_err(|e| format!("Could not prune Drive copies: {}", e))?;
+            candidates
+                .into_iter()
+                .filter(|c| !failed.contains(&c.manifest.id))
+                .collect()
+        }
+        // Without Drive, versions with cloud copies are kept so those copies stay tracked
+        None if backup_set.cloud_upload
+            || versions.iter().any(|m| m.cloud_location.is_some()) =>
+        {
+            println!(
+                "Retention for {} deferred: Google Drive not connected",
+                backup_set.name
+            );
+            return Ok(vec![]);
+        }
+        None => candidates,
+    };
+    drop(client_guard);
+
+    prune_local(manifest_manager, &candidates).map_err(|e| e.to_string())
+}
+
+/// Delete the Drive volumes and manifest of each version; returns the ids of versions whose
+/// copies could not all be removed. Volumes are deleted by the ids recorded in the version's
+/// cloud location, so uploads that kept a temporary name are removed too. The manifest's id
+/// is not recorded, so it is found by name in the full folder listing.
+async fn delete_drive_versions(
+    client: &mut GoogleDriveClient,
+    versions: &[BackupManifest],
+) -> Result<Vec<String>, DriveError> {
+    let files = client.list_backups().await?;
+    let mut failed = Vec::new();
+
+    for version in versions {
+        let archive_name = format!("backup_{}.zip", version.id);
+        let manifest_name = format!("manifest_{}.json", version.id);
+
+        let mut file_ids: Vec<&str> = match &version.cloud_location {
+            Some(location) => std::iter::once(location.file_id.as_str())
+                .chain(location.chunks.iter().map(|c| c.file_id.as_str()))
+                .collect(),
+            // Uploads without a recorded location can only be matched by their names
+            None => files
+                .iter()
+                .filter(|f| f.name.starts_with(&archive_name))
+                .map(|f| f.id.as_str())
+                .collect(),
+        };
+        file_ids.extend(
+            files
+                .iter()
+                .filter(|f| f.name == manifest_name)
+                .map(|f| f.id.as_str()),
+        );
+        file_ids.sort_unstable();
+        file_ids.dedup();
+
+        for file_id in file_ids {
+            match client.delete_file(file_id).await {
+                Ok(_) | Err(DriveError::FileNotFound(_)) => println!(
+                    "Retention: deleted {} of backup {} from Google Drive",
+                    file_id, version.id
+                ),
+                Err(e) => {
+                    eprintln!(
+                        "Retention: failed to delete {} of backup {} from Google Drive: {}",
+                        file_id, version.id, e
+                    );
+                    if !failed.contains(&version.id) {
+                        failed.push(version.id.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(failed)
+}
+
 fn resolve_drive_config(
     env_config: Option<DriveConfig>,
     provided_client_id: Option<String>,
@@ -280,6 +794,15 @@ pub async fn get_app_state(
     Ok(CommandResult::ok(manager.get_state().clone()))
 }
 
+/// Explanation for the user when the saved state had to be recovered at startup.
+#[tauri::command]
+pub async fn get_state_recovery(
+    state: State<'_, AppStateManager>,
+) -> Result<CommandResult<Option<String>>, String> {
+    let manager = state.0.lock().await;
+    Ok(CommandResult::ok(manager.recovery().map(|r| r.message())))
+}
+
 #[tauri::command]
 pub async fn is_first_run(
     state: State<'_, AppStateManager>,
@@ -344,18 +867,18 @@ pub async fn get_backup_set(
     Ok(CommandResult::ok(set))
 }
 
+/// Create a set from a name and sources. The validation report is returned either way,
+/// with the errors that rejected the set or the warnings it was saved with.
 #[tauri::command]
 pub async fn create_backup_set(
     name: String,
     sources: Vec<String>,
     state: State<'_, AppStateManager>,
-) -> Result<CommandResult<BackupSet>, String> {
-    let mut manager = state.0.lock().await;
+) -> Result<CommandResult<SavedBackupSet>, String> {
     let mut set = BackupSet::new(name);
     set.sources = sources.clone();
     set.paths = sources;
-    manager.add_backup_set(set.clone()).map_err(|e| e.to_string())?;
-    Ok(CommandResult::ok(set))
+    add_validated_set(set, &state).await
 }
 
 #[tauri::command]
@@ -363,8 +886,7 @@ pub async fn create_backup_set_from_preset(
     preset: String,
     home_dir: String,
     state: State<'_, AppStateManager>,
-) -> Result<CommandResult<BackupSet>, String> {
-    let mut manager = state.0.lock().await;
+) -> Result<CommandResult<SavedBackupSet>, String> {
     let preset = match preset.to_lowercase().as_str() {
         "documents" => BackupPreset::Documents,
         "photos" => BackupPreset::Photos,
@@ -374,22 +896,52 @@ pub async fn create_backup_set_from_preset(
     };
     
     let set = preset.create_set(&PathBuf::from(home_dir));
-    manager.add_backup_set(set.clone()).map_err(|e| e.to_string())?;
-    Ok(CommandResult::ok(set))
+    add_validated_set(set, &state).await
+}
+
+/// Store a new set unless validation finds errors.
+async fn add_validated_set(
+    set: BackupSet,
+    state: &AppStateManager,
+) -> Result<CommandResult<SavedBackupSet>, String> {
+    let validation = validate::validate_backup_set(&set);
+    let saved = SavedBackupSet { set, validation };
+    if let Some(message) = saved.validation.error_message() {
+        return Ok(CommandResult::err_with(message, saved));
+    }
+
+    let mut manager = state.0.lock().await;
+    manager
+        .add_backup_set(saved.set.clone())
+        .map_err(|e| e.to_string())?;
+    Ok(CommandResult::ok(saved))
 }
 
+/// Save changes to a set. The validation report is returned either way, with the errors
+/// that rejected the save or the warnings it was saved with.
 #[tauri::command]
 pub async fn update_backup_set(
     set: BackupSet,
     state: State<'_, AppStateManager>,
-) -> Result<CommandResult<()>, String> {
+) -> Result<CommandResult<ValidationReport>, String> {
+    let report = validate::validate_backup_set(&set);
+    if let Some(message) = report.error_message() {
+        return Ok(CommandResult::err_with(message, report));
+    }
+
     let mut manager = state.0.lock().await;
     match manager.update_backup_set(set) {
-        Ok(_) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Ok(_) => Ok(CommandResult::ok(report)),
+        Err(e) => Ok(CommandResult::err_with(e.to_string(), report)),
     }
 }
 
+/// Check a set's sources and destination without saving it.
+#[tauri::command]
+pub async fn validate_backup_set(set: BackupSet) -> Result<CommandResult<ValidationReport>, String> {
+    Ok(CommandResult::ok(validate::validate_backup_set(&set)))
+}
+
 #[tauri::command]
 pub async fn delete_backup_set(
     id: String,
@@ -402,6 +954,57 @@ pub async fn delete_backup_set(
     }
 }
 
+/// Write the chosen sets, or all of them, and their schedules to a JSON or TOML file.
+#[tauri::command]
+pub async fn export_definitions(
+    path: String,
+    backupSetIds: Option<Vec<String>>,
+    state: State<'_, AppStateManager>,
+) -> Result<CommandResult<()>, String> {
+    let manager = state.0.lock().await;
+    let app_state = manager.get_state();
+    let sets: Vec<&BackupSet> = app_state
+        .backup_sets
+        .sets
+        .iter()
+        .filter(|s| backupSetIds.as_ref().is_none_or(|ids| ids.contains(&s.id)))
+        .collect();
+
+    let file = DefinitionsFile::export(&sets, &app_state.schedules);
+    match file.write(&PathBuf::from(path)) {
+        Ok(_) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
+
+/// Add the sets and schedules of a definitions file. With `dryRun` nothing is saved and
+/// the report shows what would happen.
+#[tauri::command]
+pub async fn import_definitions(
+    path: String,
+    conflictPolicy: Option<ConflictPolicy>,
+    dryRun: Option<bool>,
+    state: State<'_, AppStateManager>,
+) -> Result<CommandResult<ImportReport>, String> {
+    let file = match DefinitionsFile::read(&PathBuf::from(path)) {
+        Ok(file) => file,
+        Err(e) => return Ok(CommandResult::err(e.to_string())),
+    };
+
+    let mut manager = state.0.lock().await;
+    let mut sets = manager.get_state().backup_sets.clone();
+    let mut schedules = manager.get_state().schedules.clone();
+    let report = file.import(&mut sets, &mut schedules, conflictPolicy.unwrap_or_default());
+
+    if !dryRun.unwrap_or(false) {
+        let app_state = manager.get_state_mut();
+        app_state.backup_sets = sets;
+        app_state.schedules = schedules;
+        manager.save().map_err(|e| e.to_string())?;
+    }
+    Ok(CommandResult::ok(report))
+}
+
 // ============= Schedule Commands =============
 
 #[tauri::command]
@@ -514,133 +1117,306 @@ pub async fn set_weather_triggers(
 #[tauri::command]
 pub async fn run_backup(
     backupSetId: String,
-    incremental: bool,
+    mode: Option<BackupMode>,
     app: AppHandle,
     state: State<'_, AppStateManager>,
     engine_state: State<'_, BackupEngineState>,
     drive_state: State<'_, DriveClientState>,
 ) -> Result<CommandResult<BackupResult>, String> {
-    println!("run_backup called with backupSetId: {}, incremental: {}", backupSetId, incremental);
-    
+    println!("run_backup called with backupSetId: {}, mode: {:?}", backupSetId, mode);
+
+    match execute_backup_with_trigger(
+        backupSetId,
+        mode,
+        "manual",
+        app,
+        state.0.clone(),
+        engine_state.0.clone(),
+        drive_state.0.clone(),
+    )
+    .await
+    {
+        Ok(result) => Ok(CommandResult::ok(result)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Page through recorded backup runs, newest first, optionally for a single set.
+#[tauri::command]
+pub async fn list_backup_runs(
+    backupSetId: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    app: AppHandle,
+) -> Result<CommandResult<BackupRunPage>, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let history = RunHistory::new(app_data_dir);
+
+    match history.list_runs(
+        backupSetId.as_deref(),
+        offset.unwrap_or(0),
+        limit.unwrap_or(50),
+    ) {
+        Ok(page) => Ok(CommandResult::ok(page)),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
+
+#[tauri::command]
+pub async fn get_backup_run(
+    runId: String,
+    app: AppHandle,
+) -> Result<CommandResult<BackupRun>, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let history = RunHistory::new(app_data_dir);
+
+    match history.load_run(&runId) {
+        Ok(Some(run)) => Ok(CommandResult::ok(run)),
+        Ok(None) => Ok(CommandResult::err(format!("Backup run {} not found", runId))),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
+
+/// Merge the latest chain of a set into a synthetic full backup and record it as a run.
+#[tauri::command]
+pub async fn synthesize_full_backup(
+    backupSetId: String,
+    app: AppHandle,
+    state: State<'_, AppStateManager>,
+    engine_state: State<'_, BackupEngineState>,
+    drive_state: State<'_, DriveClientState>,
+) -> Result<CommandResult<BackupResult>, String> {
+    let history = app.path().app_data_dir().ok().map(RunHistory::new);
+    let mut run = BackupRun::start(&backupSetId, "synthetic", BackupMode::Full);
+    save_run(history.as_ref(), &run);
+
+    let result = run_synthesis_job(
+        &backupSetId,
+        &app,
+        &state.0,
+        &engine_state.0,
+        &drive_state.0,
+        &mut run,
+    )
+    .await;
+
+    run.finish(result.as_ref().err().cloned());
+    save_run(history.as_ref(), &run);
+    match result {
+        Ok(result) => Ok(CommandResult::ok(result)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Build the synthetic full and upload it for cloud sets. The merged versions are only marked
+/// superseded once the synthetic full is stored everywhere they are, so retention never
+/// removes the last cloud copy of their data.
+async fn run_synthesis_job(
+    backup_set_id: &str,
+    app: &AppHandle,
+    state: &Arc<Mutex<StateManager>>,
+    engine_state: &Arc<Mutex<Option<BackupEngine>>>,
+    drive_state: &Arc<Mutex<Option<GoogleDriveClient>>>,
+    run: &mut BackupRun,
+) -> Result<BackupResult, String> {
+    let manager = state.lock().await;
+    let backup_set = manager
+        .get_state()
+        .backup_sets
+        .get_set(backup_set_id)
+        .cloned();
+    let chunk_size_mb = manager.get_state().settings.chunk_size_mb;
+    drop(manager);
+
+    let Some(backup_set) = backup_set else {
+        return Err("Backup set not found".to_string());
+    };
+    run.backup_set_name = Some(backup_set.name.clone());
+
+    let mut engine_guard = engine_state.lock().await;
+    let engine = engine_guard
+        .as_mut()
+        .ok_or("Backup engine not initialized")?;
+
+    let progress_handle = app.clone();
+    let backup_set_id_for_progress = backup_set_id.to_string();
+    let result = engine
+        .synthesize_full(&backup_set, move |progress| {
+            let mut value: Value = serde_json::to_value(&progress).unwrap_or(Value::Null);
+            if let Value::Object(ref mut map) = value {
+                map.insert(
+                    "backup_set_id".to_string(),
+                    Value::String(backup_set_id_for_progress.clone()),
+                );
+                map.insert("trigger".to_string(), Value::String("synthetic".to_string()));
+            }
+            let _ = progress_handle.emit("backup:progress", value);
+        })
+        .map_err(|e| e.to_string())?;
+    drop(engine_guard);
+    run.record_result(&result);
+
+    let manifest_manager = app.state::<ManifestManagerState>().0.clone();
+    if backup_set.cloud_upload {
+        // Volumes are cut before Drive is locked, keeping the engine-then-Drive lock order
+        let volumes = if drive_state.lock().await.is_some() {
+            Some(split_for_upload(engine_state, &result.archive_path, chunk_size_mb).await)
+        } else {
+            None
+        };
+        let mut client_guard = drive_state.lock().await;
+
+        let Some(client) = client_guard.as_mut() else {
+            if let Some(Ok(volumes)) = &volumes {
+                remove_split_volumes(volumes);
+            }
+            let msg = "Cloud upload skipped: Google Drive not connected; merged backups kept"
+                .to_string();
+            eprintln!("{msg}");
+            run.record_upload(RunUploadStatus::Skipped, 0, Some(msg.clone()));
+            let _ = app.emit("upload:error", msg);
+            return Ok(result);
+        };
+
+        let uploaded = match volumes {
+            Some(Ok(volumes)) => {
+                upload_archive_volumes(client, app, &manifest_manager, &result, volumes).await
+            }
+            Some(Err(e)) => Err(e),
+            None => Err("Google Drive not connected".to_string()),
+        };
+        let uploaded = match uploaded {
+            Ok(location) => {
+                run.record_upload(
+                    RunUploadStatus::Uploaded,
+                    location.chunks.len() as u32,
+                    None,
+                );
+                let manifest_uploaded = upload_manifest(client, app, &result).await;
+                if let Err(msg) = &manifest_uploaded {
+                    run.errors.push(msg.clone());
+                }
+                manifest_uploaded
+            }
+            Err(e) => {
+                let msg = format!("Cloud upload failed: {}", e);
+                run.record_upload(RunUploadStatus::Failed, 0, Some(msg.clone()));
+                Err(msg)
+            }
+        };
+        drop(client_guard);
+
+        if let Err(msg) = uploaded {
+            eprintln!("{msg}; merged backups kept");
+            let _ = app.emit("upload:error", msg);
+            return Ok(result);
+        }
+
+        if backup_set.local_destination.is_none() {
+            let _ = std::fs::remove_file(&result.archive_path);
+        }
+    }
+
+    let merged_from = manifest_manager
+        .load_manifest_by_id(&result.id)
+        .map_err(|e| e.to_string())?
+        .map(|m| m.merged_from)
+        .unwrap_or_default();
+    manifest_manager
+        .mark_superseded(&merged_from, &result.id)
+        .map_err(|e| e.to_string())?;
+
+    Ok(result)
+}
+
+/// Dry-run of retention for a set, optionally with a GFS policy that has not been saved yet.
+#[tauri::command]
+pub async fn preview_retention(
+    backupSetId: String,
+    policy: Option<GfsPolicy>,
+    state: State<'_, AppStateManager>,
+    engine_state: State<'_, BackupEngineState>,
+) -> Result<CommandResult<RetentionPlan>, String> {
+    let manager = state.0.lock().await;
+    let backup_set = manager.get_state().backup_sets.get_set(&backupSetId).cloned();
+    drop(manager);
+
+    let Some(mut backup_set) = backup_set else {
+        return Ok(CommandResult::err("Backup set not found".to_string()));
+    };
+    if policy.is_some() {
+        backup_set.gfs_policy = policy;
+    }
+
+    let engine_guard = engine_state.0.lock().await;
+    let engine = engine_guard.as_ref().ok_or("Backup engine not initialized")?;
+
+    match plan_retention(&backup_set, engine.manifest_manager(), Utc::now()) {
+        Ok(plan) => Ok(CommandResult::ok(plan)),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
+
+/// Estimate the size of the next backup of a set and check it fits on the temp and destination disks.
+#[tauri::command]
+pub async fn estimate_backup(
+    backupSetId: String,
+    mode: Option<BackupMode>,
+    state: State<'_, AppStateManager>,
+    engine_state: State<'_, BackupEngineState>,
+    drive_state: State<'_, DriveClientState>,
+) -> Result<CommandResult<SizeEstimate>, String> {
     let manager = state.0.lock().await;
     let backup_set = manager.get_state().backup_sets.get_set(&backupSetId).cloned();
+    let settings = manager.get_state().settings.clone();
     drop(manager);
 
     let Some(backup_set) = backup_set else {
-        println!("Backup set not found: {}", backupSetId);
         return Ok(CommandResult::err("Backup set not found".to_string()));
     };
 
-    println!("Found backup set: {} with {} sources", backup_set.name, backup_set.sources.len());
+    // Mirrors the choice made in execute_backup_with_trigger
+    let streaming = settings.stream_uploads
+        && backup_set.cloud_upload
+        && backup_set.local_destination.is_none()
+        && drive_state.0.lock().await.is_some();
 
     let mut engine_guard = engine_state.0.lock().await;
     let engine = engine_guard.as_mut().ok_or("Backup engine not initialized")?;
-    
-    println!("Backup engine initialized successfully");
-
-    let progress_handle = app.clone();
-    let backup_set_id_for_progress = backupSetId.clone();
-    let result = engine.execute_backup(&backup_set, incremental, move |progress| {
-        let mut value: Value = serde_json::to_value(&progress).unwrap_or(Value::Null);
-        if let Value::Object(ref mut map) = value {
-            map.insert(
-                "backup_set_id".to_string(),
-                Value::String(backup_set_id_for_progress.clone()),
-            );
-            map.insert("trigger".to_string(), Value::String("manual".to_string()));
-        }
-        let _ = progress_handle.emit("backup:progress", value);
-    });
-
-    match result {
-        Ok(result) => {
-            let no_changes = result.total_bytes == 0 && result.total_files == 0;
+    engine.set_chunk_size_mb(settings.chunk_size_mb);
 
-            if !no_changes {
-                // Handle cloud upload if enabled
-                if backup_set.cloud_upload {
-                    let mut client_guard = drive_state.0.lock().await;
+    match engine.estimate_backup(&backup_set, mode.unwrap_or(backup_set.mode), streaming) {
+        Ok(estimate) => Ok(CommandResult::ok(estimate)),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
 
-                    if let Some(client) = client_guard.as_mut() {
-                        if !result.archive_path.exists() {
-                            let msg = format!(
-                                "Archive path missing for upload: {:?}",
-                                result.archive_path
-                            );
-                            eprintln!("{msg}");
-                            let _ = app.emit("upload:error", msg);
-                        } else {
-                            let archive_name = format!("backup_{}.zip", result.id);
-                            let progress_handle = app.clone();
-                            let error_handle = app.clone();
-                            match client
-                                .upload_file(&result.archive_path, &archive_name, move |progress| {
-                                    let _ = progress_handle.emit("upload:progress", progress);
-                                })
-                                .await
-                            {
-                                Ok(_drive_file) => {
-                                    println!("Archive uploaded successfully");
-
-                                    // Upload Manifest
-                                    if let Ok(app_data_dir) = app.path().app_data_dir() {
-                                        let manifest_path = app_data_dir
-                                            .join("manifests")
-                                            .join(format!("{}.json", result.id));
-                                        if manifest_path.exists() {
-                                            let manifest_name =
-                                                format!("manifest_{}.json", result.id);
-                                            if let Err(e) =
-                                                client.upload_file(&manifest_path, &manifest_name, |_| {}).await
-                                            {
-                                                let msg =
-                                                    format!("Failed to upload manifest: {}", e);
-                                                eprintln!("{msg}");
-                                                let _ = error_handle.emit("upload:error", msg);
-                                            } else {
-                                                println!("Manifest uploaded successfully");
-                                            }
-                                        } else {
-                                            let msg = format!(
-                                                "Manifest file not found at {:?}",
-                                                manifest_path
-                                            );
-                                            eprintln!("{msg}");
-                                            let _ = error_handle.emit("upload:error", msg);
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    let msg = format!("Cloud upload failed: {}", e);
-                                    eprintln!("{msg}");
-                                    let _ = error_handle.emit("upload:error", msg);
-                                }
-                            }
-                        }
-                    } else {
-                        let msg = "Cloud upload skipped: Google Drive not connected".to_string();
-                        eprintln!("{msg}");
-                        let _ = app.emit("upload:error", msg);
-                    }
-                }
+/// Compare a set's sources with its latest backup to show what is not protected yet.
+#[tauri::command]
+pub async fn get_protection_status(
+    backupSetId: String,
+    state: State<'_, AppStateManager>,
+    manifest_state: State<'_, ManifestManagerState>,
+) -> Result<CommandResult<ProtectionStatus>, String> {
+    let manager = state.0.lock().await;
+    let backup_set = manager.get_state().backup_sets.get_set(&backupSetId).cloned();
+    drop(manager);
 
-                // Clean up temp file if it's in temp dir (when no local destination)
-                if backup_set.local_destination.is_none() {
-                    let _ = std::fs::remove_file(&result.archive_path);
-                }
+    let Some(backup_set) = backup_set else {
+        return Ok(CommandResult::err("Backup set not found".to_string()));
+    };
 
-                // Update backup set stats
-                let mut manager = state.0.lock().await;
-                if let Some(set) = manager.get_state_mut().backup_sets.get_set_mut(&backupSetId) {
-                    set.record_backup(result.total_bytes);
-                }
-                manager.save().ok();
-            } else {
-                println!("Backup skipped: no changes detected for {}", backup_set.name);
-            }
-            
-            Ok(CommandResult::ok(result))
-        }
+    // Scanning and hashing can take a while; keep it off the async runtime and away from
+    // the engine lock so running backups are not held up
+    let manifest_manager = manifest_state.0.clone();
+    let status = tokio::task::spawn_blocking(move || {
+        BackupEngine::protection_status(&manifest_manager, &backup_set)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    match status {
+        Ok(status) => Ok(CommandResult::ok(status)),
         Err(e) => Ok(CommandResult::err(e.to_string())),
     }
 }
@@ -870,6 +1646,7 @@ pub async fn list_drive_backups(
 #[tauri::command]
 pub async fn list_drive_backup_bundles(
     drive_state: State<'_, DriveClientState>,
+    manifest_state: State<'_, ManifestManagerState>,
 ) -> Result<CommandResult<Vec<CloudBackupBundle>>, String> {
     let (client_template, files) = {
         let mut client_guard = drive_state.0.lock().await;
@@ -901,22 +1678,35 @@ pub async fn list_drive_backup_bundles(
                 .trim_end_matches(".json")
                 .to_string();
             let archive_name = format!("backup_{}.zip", manifest_id);
+            let volume_prefix = format!("{}.part", archive_name);
 
-            let archive_file = files
+            let mut volumes: Vec<DriveFile> = files
                 .iter()
-                .find(|f| f.name == archive_name)
-                .cloned();
-
-            let Some(archive_file) = archive_file else {
-                return None;
+                .filter(|f| f.name.starts_with(&volume_prefix))
+                .cloned()
+                .collect();
+            volumes.sort_by(|a, b| a.name.cmp(&b.name));
+
+            // Multi-volume archives are presented as one logical file named after the archive
+            let archive_file = match files.iter().find(|f| f.name == archive_name) {
+                Some(file) => file.clone(),
+                None => {
+                    let first = volumes.first()?;
+                    DriveFile {
+                        name: archive_name,
+                        size: Some(volumes.iter().filter_map(|v| v.size).sum()),
+                        ..first.clone()
+                    }
+                }
             };
 
             match client.download_bytes(&manifest_file.id).await {
-                Ok(bytes) => match serde_json::from_slice::<BackupManifest>(&bytes) {
+                Ok(bytes) => match BackupManifest::from_slice(&bytes) {
                     Ok(manifest) => Some(CloudBackupBundle {
                         manifest,
                         manifest_file: manifest_file.clone(),
                         archive_file,
+                        volumes,
                     }),
                     Err(e) => {
                         eprintln!(
@@ -938,9 +1728,98 @@ pub async fn list_drive_backup_bundles(
     .collect::<Vec<_>>()
     .await;
 
+    // Keep the manifests so file search covers versions that only exist in Drive
+    for bundle in &bundles {
+        if let Err(e) = manifest_state.0.cache_cloud_manifest(&bundle.manifest) {
+            eprintln!("Failed to cache manifest {}: {}", bundle.manifest.id, e);
+        }
+    }
+
     Ok(CommandResult::ok(bundles))
 }
 
+/// Disaster recovery: copy every manifest in the Drive backup folder into the local store,
+/// rebuild the catalog and recreate the sets they belong to, so their versions can be
+/// browsed and restored on a machine that has no record of them.
+#[tauri::command]
+pub async fn recover_from_cloud(
+    state: State<'_, AppStateManager>,
+    drive_state: State<'_, DriveClientState>,
+    manifest_state: State<'_, ManifestManagerState>,
+) -> Result<CommandResult<RecoveryReport>, String> {
+    let (client_template, files) = {
+        let mut client_guard = drive_state.0.lock().await;
+        let client = client_guard
+            .as_mut()
+            .ok_or("Google Drive client not initialized")?;
+
+        // A listing that fails part way is reported rather than recovering only what was seen
+        match client.list_backups().await {
+            Ok(files) => (client.clone(), files),
+            Err(e) => {
+                eprintln!("Failed to list Google Drive backups: {}", e);
+                return Ok(CommandResult::err(e.to_string()));
+            }
+        }
+    };
+
+    let manifest_files: Vec<DriveFile> = files
+        .into_iter()
+        .filter(|f| f.name.starts_with("manifest_") && f.name.ends_with(".json"))
+        .collect();
+    println!("Recovering {} manifests from Google Drive", manifest_files.len());
+
+    let downloads: Vec<Result<BackupManifest, RecoveryFailure>> =
+        stream::iter(manifest_files.into_iter().map(|manifest_file| {
+            let mut client = client_template.clone();
+            async move {
+                client
+                    .download_bytes(&manifest_file.id)
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|bytes| {
+                        BackupManifest::from_slice(&bytes).map_err(|e| e.to_string())
+                    })
+                    .map_err(|error| RecoveryFailure {
+                        name: manifest_file.name,
+                        error,
+                    })
+            }
+        }))
+        .buffer_unordered(4)
+        .collect()
+        .await;
+    let mut found = FoundBackups::default();
+    for download in downloads {
+        match download {
+            Ok(manifest) => found.manifests.push(manifest),
+            Err(failure) => {
+                eprintln!("Failed to recover manifest {}: {}", failure.name, failure.error);
+                found.failures.push(failure);
+            }
+        }
+    }
+
+    let mut manager = state.0.lock().await;
+    let mut sets = manager.get_state().backup_sets.clone();
+    let report = match recover_manifests(&manifest_state.0, &mut sets, found, "Google Drive") {
+        Ok(report) => report,
+        Err(e) => return Ok(CommandResult::err(e.to_string())),
+    };
+
+    if !report.backup_sets_created.is_empty() {
+        manager.get_state_mut().backup_sets = sets;
+        manager.save().map_err(|e| e.to_string())?;
+    }
+    println!(
+        "Recovered {} of {} manifests from Google Drive, recreated {} backup sets",
+        report.manifests_imported,
+        report.manifests_found,
+        report.backup_sets_created.len()
+    );
+    Ok(CommandResult::ok(report))
+}
+
 #[tauri::command]
 pub async fn download_backup_bundle(
     manifestFileId: String,
@@ -970,22 +1849,43 @@ pub async fn download_backup_bundle(
         .await
         .map_err(|e| e.to_string())?;
 
-    let download_handle = app.clone();
-    client
-        .download_file(&archiveFileId, &archive_path, move |downloaded, total| {
-            let _ = download_handle.emit(
-                "download:progress",
-                serde_json::json!({
-                    "downloaded": downloaded,
-                    "total": total,
-                    "fileName": archive_name,
-                    "targetPath": archive_path_string,
-                    "fileId": archive_id
-                }),
-            );
-        })
-        .await
-        .map_err(|e| e.to_string())?;
+    let chunks = std::fs::read(&manifest_path)
+        .ok()
+        .and_then(|bytes| BackupManifest::from_slice(&bytes).ok())
+        .and_then(|manifest| manifest.cloud_location)
+        .map(|location| location.chunks)
+        .unwrap_or_default();
+
+    let downloaded = if chunks.len() > 1 {
+        download_archive_volumes(client, &app, chunks, &archive_path, &archive_name).await
+    } else {
+        let download_handle = app.clone();
+        let result = client
+            .download_file(&archiveFileId, &archive_path, move |downloaded, total| {
+                let _ = download_handle.emit(
+                    "download:progress",
+                    serde_json::json!({
+                        "downloaded": downloaded,
+                        "total": total,
+                        "fileName": archive_name,
+                        "targetPath": archive_path_string,
+                        "fileId": archive_id
+                    }),
+                );
+            })
+            .await
+            .map_err(|e| e.to_string());
+
+        match (result, chunks.first()) {
+            (Ok(()), Some(chunk)) => verify_volume(&archive_path, chunk),
+            (result, _) => result,
+        }
+    };
+
+    if let Err(e) = downloaded {
+        let _ = std::fs::remove_file(&archive_path);
+        return Ok(CommandResult::err(e));
+    }
 
     Ok(CommandResult::ok((
         manifest_path.to_string_lossy().to_string(),
@@ -993,6 +1893,73 @@ pub async fn download_backup_bundle(
     )))
 }
 
+/// Check a downloaded volume against the size and hash recorded at upload time.
+fn verify_volume(path: &std::path::Path, chunk: &CloudChunk) -> Result<(), String> {
+    let size = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    let hash = BackupEngine::calculate_hash(path).map_err(|e| e.to_string())?;
+
+    if size != chunk.size || hash != chunk.hash {
+        return Err(format!(
+            "Volume {} failed verification (expected {} bytes, sha256 {})",
+            chunk.index, chunk.size, chunk.hash
+        ));
+    }
+    Ok(())
+}
+
+/// Download every volume of an archive, verify each one and append it to `archive_path`.
+async fn download_archive_volumes(
+    client: &mut GoogleDriveClient,
+    app: &AppHandle,
+    mut chunks: Vec<CloudChunk>,
+    archive_path: &std::path::Path,
+    archive_name: &str,
+) -> Result<(), String> {
+    chunks.sort_by_key(|c| c.index);
+    let total: u64 = chunks.iter().map(|c| c.size).sum();
+    let target_path = archive_path.to_string_lossy().to_string();
+    let mut archive = std::fs::File::create(archive_path).map_err(|e| e.to_string())?;
+    let mut completed: u64 = 0;
+
+    for chunk in &chunks {
+        let volume_path = PathBuf::from(format!("{}.part{:03}", target_path, chunk.index));
+        let download_handle = app.clone();
+        let file_name = archive_name.to_string();
+        let target = target_path.clone();
+        let file_id = chunk.file_id.clone();
+
+        let downloaded = client
+            .download_file(&chunk.file_id, &volume_path, move |downloaded, _| {
+                let _ = download_handle.emit(
+                    "download:progress",
+                    serde_json::json!({
+                        "downloaded": completed + downloaded,
+                        "total": total,
+                        "fileName": file_name,
+                        "targetPath": target,
+                        "fileId": file_id
+                    }),
+                );
+            })
+            .await
+            .map_err(|e| e.to_string());
+
+        // The volume file is removed whether or not it arrived intact
+        let appended = downloaded
+            .and_then(|_| verify_volume(&volume_path, chunk))
+            .and_then(|_| {
+                let mut volume = std::fs::File::open(&volume_path).map_err(|e| e.to_string())?;
+                std::io::copy(&mut volume, &mut archive).map_err(|e| e.to_string())
+            });
+        let _ = std::fs::remove_file(&volume_path);
+        appended?;
+
+        completed += chunk.size;
+    }
+
+    archive.sync_all().map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn download_from_drive(
     file_id: String,
@@ -1031,10 +1998,27 @@ pub async fn download_from_drive(
 pub async fn delete_from_drive(
     file_id: String,
     drive_state: State<'_, DriveClientState>,
+    manifest_state: State<'_, ManifestManagerState>,
 ) -> Result<CommandResult<()>, String> {
     let mut client_guard = drive_state.0.lock().await;
     let client = client_guard.as_mut().ok_or("Google Drive client not initialized")?;
-    
+
+    // Manifests are only known by name, so look the file up to protect pinned ones too
+    let file_name = match client.list_backups().await {
+        Ok(files) => files.into_iter().find(|f| f.id == file_id).map(|f| f.name),
+        Err(e) => return Ok(CommandResult::err(e.to_string())),
+    };
+    match manifest_state.0.pinned_cloud_owner(&file_id, file_name.as_deref()) {
+        Ok(Some(manifest_id)) => {
+            return Ok(CommandResult::err(format!(
+                "This file belongs to pinned backup {}; unpin it before deleting",
+                manifest_id
+            )))
+        }
+        Ok(None) => {}
+        Err(e) => return Ok(CommandResult::err(e.to_string())),
+    }
+
     match client.delete_file(&file_id).await {
         Ok(_) => Ok(CommandResult::ok(())),
         Err(e) => Ok(CommandResult::err(e.to_string())),
@@ -1126,14 +2110,296 @@ pub async fn set_location(
 
 // ============= Manifest Commands =============
 
+/// Versions recorded for a set, newest first.
 #[tauri::command]
 pub async fn get_manifests_for_set(
-    _backup_set_id: String,
-    _engine_state: State<'_, BackupEngineState>,
+    backupSetId: String,
+    manifest_state: State<'_, ManifestManagerState>,
 ) -> Result<CommandResult<Vec<ManifestSummary>>, String> {
-    // This would need the manifest manager exposed differently
-    // For now return empty - will be implemented with proper state management
-    Ok(CommandResult::ok(vec![]))
+    match manifest_state.0.list_manifests_for_set(&backupSetId) {
+        Ok(mut manifests) => {
+            manifests.sort_by_key(|m| std::cmp::Reverse(m.created_at));
+            Ok(CommandResult::ok(manifests))
+        }
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
+
+/// A version with one page of its files.
+#[tauri::command]
+pub async fn get_manifest(
+    manifestId: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    manifest_state: State<'_, ManifestManagerState>,
+) -> Result<CommandResult<ManifestDetail>, String> {
+    match manifest_state.0.manifest_detail(
+        &manifestId,
+        offset.unwrap_or(0),
+        limit.unwrap_or(200),
+    ) {
+        Ok(detail) => Ok(CommandResult::ok(detail)),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
+
+/// Find every stored version of files matching `query`, in local and cached Drive manifests.
+#[tauri::command]
+pub async fn search_backed_up_files(
+    query: FileSearchQuery,
+    manifest_state: State<'_, ManifestManagerState>,
+) -> Result<CommandResult<FileSearchResult>, String> {
+    match search_files(&manifest_state.0, &query) {
+        Ok(result) => Ok(CommandResult::ok(result)),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
+
+/// Added, removed, modified and renamed files between two versions of a set.
+#[tauri::command]
+pub async fn diff_manifests(
+    fromId: String,
+    toId: String,
+    manifest_state: State<'_, ManifestManagerState>,
+) -> Result<CommandResult<ManifestDiff>, String> {
+    match diff::diff_manifests(&manifest_state.0, &fromId, &toId) {
+        Ok(diff) => Ok(CommandResult::ok(diff)),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
+
+/// Metadata of a version without its file list.
+#[tauri::command]
+pub async fn get_manifest_version(
+    manifestId: String,
+    manifest_state: State<'_, ManifestManagerState>,
+) -> Result<CommandResult<VersionInfo>, String> {
+    match manifest_state.0.version_info(&manifestId) {
+        Ok(info) => Ok(CommandResult::ok(info)),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
+
+/// Regenerate the manifest catalog from the manifest files on disk.
+#[tauri::command]
+pub async fn rebuild_manifest_index(
+    manifest_state: State<'_, ManifestManagerState>,
+) -> Result<CommandResult<IndexRebuild>, String> {
+    match manifest_state.0.rebuild_index() {
+        Ok(rebuild) => Ok(CommandResult::ok(rebuild)),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
+
+/// Put a version on hold so retention and cleanup never delete it.
+#[tauri::command]
+pub async fn pin_backup(
+    manifestId: String,
+    label: Option<String>,
+    engine_state: State<'_, BackupEngineState>,
+) -> Result<CommandResult<BackupManifest>, String> {
+    let engine_guard = engine_state.0.lock().await;
+    let engine = engine_guard.as_ref().ok_or("Backup engine not initialized")?;
+
+    match engine.manifest_manager().set_pinned(&manifestId, true, label) {
+        Ok(manifest) => Ok(CommandResult::ok(manifest)),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
+
+#[tauri::command]
+pub async fn unpin_backup(
+    manifestId: String,
+    engine_state: State<'_, BackupEngineState>,
+) -> Result<CommandResult<BackupManifest>, String> {
+    let engine_guard = engine_state.0.lock().await;
+    let engine = engine_guard.as_ref().ok_or("Backup engine not initialized")?;
+
+    match engine.manifest_manager().set_pinned(&manifestId, false, None) {
+        Ok(manifest) => Ok(CommandResult::ok(manifest)),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
+
+/// Import a folder of archives, such as a backup disk plugged into a new machine. Each
+/// `{set_id}_{uuid}.zip` is read into a manifest, indexed, and missing sets are recreated.
+#[tauri::command]
+pub async fn recover_from_folder(
+    path: String,
+    state: State<'_, AppStateManager>,
+    manifest_state: State<'_, ManifestManagerState>,
+) -> Result<CommandResult<RecoveryReport>, String> {
+    let folder = PathBuf::from(&path);
+    println!("Recovering archives from {}", folder.display());
+    let found = match scan_archive_folder(&folder) {
+        Ok(scan) => scan,
+        Err(e) => {
+            eprintln!("Failed to scan {}: {}", folder.display(), e);
+            return Ok(CommandResult::err(e.to_string()));
+        }
+    };
+
+    let mut manager = state.0.lock().await;
+    let mut sets = manager.get_state().backup_sets.clone();
+    let report = match recover_manifests(
+        &manifest_state.0,
+        &mut sets,
+        found,
+        &folder.display().to_string(),
+    ) {
+        Ok(report) => report,
+        Err(e) => return Ok(CommandResult::err(e.to_string())),
+    };
+
+    if !report.backup_sets_created.is_empty() {
+        manager.get_state_mut().backup_sets = sets;
+        manager.save().map_err(|e| e.to_string())?;
+    }
+    println!(
+        "Recovered {} of {} archives from {}, recreated {} backup sets",
+        report.manifests_imported,
+        report.manifests_found,
+        folder.display(),
+        report.backup_sets_created.len()
+    );
+    Ok(CommandResult::ok(report))
+}
+
+/// Download the archives of a version's chain that are only in Drive, e.g. after
+/// `recover_from_cloud`, and record where they were saved.
+async fn fetch_cloud_archives(
+    app: &AppHandle,
+    drive_state: &DriveClientState,
+    manifests: &ManifestManager,
+    manifest_id: &str,
+) -> Result<(), String> {
+    let missing: Vec<BackupManifest> = manifests
+        .load_chain(manifest_id)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|m| !m.archive_path.as_ref().is_some_and(|p| p.exists()))
+        .filter(|m| m.cloud_location.is_some())
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let cache_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("restore_cache");
+    std::fs::create_dir_all(&cache_dir).map_err(|e| e.to_string())?;
+
+    let mut client_guard = drive_state.0.lock().await;
+    let client = client_guard
+        .as_mut()
+        .ok_or("This backup is stored in Google Drive; connect Google Drive to restore it")?;
+
+    for manifest in missing {
+        let Some(location) = manifest.cloud_location else {
+            continue;
+        };
+        let archive_name = format!("backup_{}.zip", manifest.id);
+        let archive_path = cache_dir.join(&archive_name);
+
+        let downloaded = if location.chunks.len() > 1 {
+            download_archive_volumes(client, app, location.chunks, &archive_path, &archive_name)
+                .await
+        } else {
+            let result = client
+                .download_file(&location.file_id, &archive_path, |_a, _b| {})
+                .await
+                .map_err(|e| e.to_string());
+            match (result, location.chunks.first()) {
+                (Ok(()), Some(chunk)) => verify_volume(&archive_path, chunk),
+                (result, _) => result,
+            }
+        };
+        if let Err(e) = downloaded {
+            let _ = std::fs::remove_file(&archive_path);
+            return Err(format!(
+                "Could not download the archive of backup {}: {}",
+                manifest.id, e
+            ));
+        }
+
+        manifests
+            .set_archive_path(&manifest.id, &archive_path)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Restore a backup version, including the versions it builds on.
+/// Archives that are only in Drive are downloaded first.
+/// Without a target folder files go back where they came from; `sourceTargets` sends
+/// individual sources, keyed by label, to folders of their own.
+#[tauri::command]
+pub async fn restore_backup(
+    manifestId: String,
+    targetPath: Option<String>,
+    sourceTargets: Option<HashMap<String, String>>,
+    app: AppHandle,
+    engine_state: State<'_, BackupEngineState>,
+    drive_state: State<'_, DriveClientState>,
+    manifest_state: State<'_, ManifestManagerState>,
+) -> Result<CommandResult<RestoreResult>, String> {
+    if let Err(e) = fetch_cloud_archives(&app, &drive_state, &manifest_state.0, &manifestId).await
+    {
+        return Ok(CommandResult::err(e));
+    }
+
+    let engine_guard = engine_state.0.lock().await;
+    let engine = engine_guard.as_ref().ok_or("Backup engine not initialized")?;
+
+    let options = RestoreOptions {
+        target: targetPath.as_deref().map(decode_path),
+        source_targets: sourceTargets
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(label, path)| (label, decode_path(&path)))
+            .collect(),
+    };
+
+    match engine.restore_backup(&manifestId, &options) {
+        Ok(result) => Ok(CommandResult::ok(result)),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
+
+/// Restore from an archive file alone, using the manifest embedded in it. Archives of the
+/// versions it builds on must be in the same folder.
+#[tauri::command]
+pub async fn restore_from_archive(
+    archivePath: String,
+    targetPath: Option<String>,
+    sourceTargets: Option<HashMap<String, String>>,
+) -> Result<CommandResult<RestoreResult>, String> {
+    let options = RestoreOptions {
+        target: targetPath.as_deref().map(decode_path),
+        source_targets: sourceTargets
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(label, path)| (label, decode_path(&path)))
+            .collect(),
+    };
+
+    match BackupEngine::restore_archive(&decode_path(&archivePath), &options) {
+        Ok(result) => Ok(CommandResult::ok(result)),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
+}
+
+/// Check every file in an archive against the manifest embedded in it.
+#[tauri::command]
+pub async fn verify_archive(
+    archivePath: String,
+) -> Result<CommandResult<ArchiveVerification>, String> {
+    match descriptor::verify_archive(&decode_path(&archivePath)) {
+        Ok(verification) => Ok(CommandResult::ok(verification)),
+        Err(e) => Ok(CommandResult::err(e.to_string())),
+    }
 }
 
 // ============= System Commands =============
@@ -1167,6 +2433,24 @@ pub fn pick_directories() -> Vec<PathBuf> {
         .unwrap_or_default()
 }
 
+#[tauri::command]
+pub fn pick_definitions_file() -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .set_title("Import Backup Definitions")
+        .add_filter("Backup definitions", &["json", "toml"])
+        .pick_file()
+}
+
+#[tauri::command]
+pub fn pick_definitions_export_path() -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .set_title("Export Backup Definitions")
+        .set_file_name("sentry-definitions.json")
+        .add_filter("JSON", &["json"])
+        .add_filter("TOML", &["toml"])
+        .save_file()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FolderStats {
     pub file_count: u64,
@@ -3,15 +3,17 @@
 //! A high-performance desktop application for managing backups with:
 //! - Scheduled and weather-triggered backups
 //! - Google Drive cloud storage integration
-//! - Incremental backup support with manifests
+//! - Full, incremental and differential backups with manifests
 //! - System tray integration
 
 pub mod backup;
 pub mod cloud;
 pub mod commands;
+pub mod persist;
 pub mod state;
 pub mod weather;
 
+use backup::set::BackupMode;
 use commands::*;
 use state::StateManager;
 use std::sync::Arc;
@@ -57,10 +59,26 @@ pub fn run() {
 
             // Initialize state manager
             let mut state_manager = StateManager::new(data_dir.clone());
-            state_manager.load().ok();
+            if let Err(e) = state_manager.load() {
+                eprintln!("Failed to load app state: {}", e);
+            }
+            if let Some(recovery) = state_manager.recovery() {
+                eprintln!("{}", recovery.message());
+            }
 
-            // Initialize backup engine
-            let backup_engine = match backup::engine::BackupEngine::new(data_dir.clone()) {
+            // No run can be in progress yet, so running records were cut short by an exit
+            match backup::history::RunHistory::new(data_dir.clone()).mark_interrupted() {
+                Ok(0) => {}
+                Ok(count) => println!("Marked {} unfinished backup runs as interrupted", count),
+                Err(e) => eprintln!("Failed to check unfinished backup runs: {}", e),
+            }
+
+            // Initialize backup engine around a manifest manager shared with the commands
+            let manifest_manager = Arc::new(backup::manifest::ManifestManager::new(data_dir.clone()));
+            let backup_engine = match backup::engine::BackupEngine::with_manifest_manager(
+                data_dir.clone(),
+                manifest_manager.clone(),
+            ) {
                 Ok(engine) => {
                     println!("Backup engine initialized successfully");
                     Some(engine)
@@ -111,6 +129,7 @@ pub fn run() {
             app.manage(BackupEngineState(engine_arc.clone()));
             app.manage(DriveClientState(drive_arc.clone()));
             app.manage(WeatherServiceState(Arc::new(Mutex::new(weather_service))));
+            app.manage(ManifestManagerState(manifest_manager));
 
             // Spawn schedule worker to process due schedules
             let schedule_state = state_arc.clone();
@@ -124,13 +143,13 @@ pub fn run() {
                     // Poll every 3s to minimize delay between target time and execution.
                     let interval_seconds: u64 = 3;
 
-                    let due: Vec<(String, String)> = {
+                    let due: Vec<(String, String, Option<BackupMode>)> = {
                         let mgr = schedule_state.lock().await;
                         mgr.get_state()
                             .schedules
                             .iter()
                             .filter(|s| s.enabled && s.should_run_now())
-                            .map(|s| (s.id.clone(), s.backup_set_id.clone()))
+                            .map(|s| (s.id.clone(), s.backup_set_id.clone(), s.mode))
                             .collect()
                     };
 
@@ -138,7 +157,7 @@ pub fn run() {
                         println!("Schedule worker: {} due backup(s)", due.len());
                     }
 
-                    for (schedule_id, backup_set_id) in due {
+                    for (schedule_id, backup_set_id, mode) in due {
                         if running.contains(&schedule_id) {
                             continue;
                         }
@@ -146,7 +165,7 @@ pub fn run() {
 
                         let run_result = execute_backup_with_trigger(
                             backup_set_id.clone(),
-                            false,
+                            mode,
                             "schedule",
                             app_handle.clone(),
                             schedule_state.clone(),
@@ -185,6 +204,38 @@ pub fn run() {
                 }
             });
 
+            // Spawn retention worker so expired versions are pruned even without new backups
+            let retention_state = state_arc.clone();
+            let retention_engine = engine_arc.clone();
+            let retention_drive = drive_arc.clone();
+
+            tauri::async_runtime::spawn(async move {
+                let interval_seconds: u64 = 60 * 60;
+                loop {
+                    // Wait first so nothing is pruned at startup before the user can review it
+                    sleep(Duration::from_secs(interval_seconds)).await;
+
+                    let sets: Vec<backup::set::BackupSet> = {
+                        let mgr = retention_state.lock().await;
+                        mgr.get_state().backup_sets.sets.clone()
+                    };
+
+                    for set in sets.iter().filter(|s| s.enabled) {
+                        match enforce_retention(set, &retention_engine, &retention_drive).await {
+                            Ok(pruned) if !pruned.is_empty() => {
+                                println!(
+                                    "Retention worker: pruned {} version(s) of {}",
+                                    pruned.len(),
+                                    set.name
+                                );
+                            }
+                            Ok(_) => {}
+                            Err(e) => eprintln!("Retention worker failed for {}: {}", set.name, e),
+                        }
+                    }
+                }
+            });
+
             // Setup system tray
             let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
             let show = MenuItem::with_id(app, "show", "Show Window", true, None::<&str>)?;
@@ -228,6 +279,7 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             // App state
             get_app_state,
+            get_state_recovery,
             is_first_run,
             update_settings,
             update_onboarding,
@@ -238,7 +290,10 @@ pub fn run() {
             create_backup_set,
             create_backup_set_from_preset,
             update_backup_set,
+            validate_backup_set,
             delete_backup_set,
+            export_definitions,
+            import_definitions,
             // Schedules
             get_schedules,
             create_schedule,
@@ -247,6 +302,12 @@ pub fn run() {
             set_weather_triggers,
             // Backup execution
             run_backup,
+            list_backup_runs,
+            get_backup_run,
+            synthesize_full_backup,
+            preview_retention,
+            estimate_backup,
+            get_protection_status,
             // Google Drive
             get_google_auth_url,
             exchange_google_code,
@@ -258,6 +319,7 @@ pub fn run() {
             list_drive_backup_bundles,
             download_from_drive,
             download_backup_bundle,
+            recover_from_cloud,
             delete_from_drive,
             get_drive_quota,
             // Weather
@@ -267,11 +329,24 @@ pub fn run() {
             set_location,
             // Manifests
             get_manifests_for_set,
+            get_manifest,
+            get_manifest_version,
+            rebuild_manifest_index,
+            recover_from_folder,
+            search_backed_up_files,
+            diff_manifests,
+            pin_backup,
+            unpin_backup,
+            restore_backup,
+            restore_from_archive,
+            verify_archive,
             // System
             get_home_directory,
             get_documents_directory,
             pick_directory,
             pick_directories,
+            pick_definitions_file,
+            pick_definitions_export_path,
             get_folder_stats,
         ])
         .run(tauri::generate_context!())
@@ -0,0 +1,63 @@
+//! Crash-safe file writes
+//!
+//! Files are written to a temporary sibling, flushed to disk and renamed over the target,
+//! so readers see either the old or the new contents, never a torn mix.
+
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use uuid::Uuid;
+
+/// Replace `path` with what `write` produces. The previous contents stay in place if
+/// writing fails or the process dies part way.
+pub fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let temp_path = dir.join(format!(".{}.{}.tmp", name, Uuid::new_v4()));
+
+    let result = (|| {
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        write(&mut writer)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        drop(writer);
+        fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+
+    sync_dir(dir);
+    Ok(())
+}
+
+/// Atomically replace `path` with `value` as pretty-printed JSON.
+pub fn write_json_atomic<T: Serialize + ?Sized>(path: &Path, value: &T) -> io::Result<()> {
+    write_atomic(path, |writer| {
+        serde_json::to_writer_pretty(writer, value).map_err(io::Error::from)
+    })
+}
+
+/// Persist the rename itself. Windows has no directory handles to flush, and a failure
+/// here only weakens durability, so it is not reported.
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
@@ -3,14 +3,18 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::fs::{self, File};
-use std::io::{BufReader, BufWriter};
-use std::path::PathBuf;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
 
 use crate::backup::scheduler::Schedule;
 use crate::backup::set::BackupSetManager;
 use crate::cloud::google_drive::{DriveConfig, GoogleTokens};
+use crate::persist::write_json_atomic;
 use crate::weather::Location;
 
+/// Previous good copies of the state file kept next to it
+const STATE_BACKUPS: usize = 5;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppSettings {
     pub theme: Theme,
@@ -25,6 +29,9 @@ pub struct AppSettings {
     pub backup_check_interval_minutes: u32,
     pub max_concurrent_uploads: u32,
     pub chunk_size_mb: u32,
+    /// Stream cloud-only backups into Drive instead of writing the full archive to temp first
+    #[serde(default)]
+    pub stream_uploads: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -49,6 +56,7 @@ impl Default for AppSettings {
             backup_check_interval_minutes: 5,
             max_concurrent_uploads: 2,
             chunk_size_mb: 10,
+            stream_uploads: false,
         }
     }
 }
@@ -111,9 +119,50 @@ impl Default for AppState {
     }
 }
 
+/// What happened when the state file could not be read at startup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateRecovery {
+    /// Why the state file was rejected
+    pub error: String,
+    /// Where the unreadable file was moved, kept for manual recovery
+    pub corrupt_copy: Option<PathBuf>,
+    /// Backup copy the state was restored from; `None` if no copy was usable and the
+    /// defaults were loaded
+    pub restored_from: Option<PathBuf>,
+    /// When the restored copy was last saved
+    pub restored_state_time: Option<DateTime<Utc>>,
+}
+
+impl StateRecovery {
+    /// Message for the user
+    pub fn message(&self) -> String {
+        match (&self.restored_from, self.restored_state_time) {
+            (Some(_), Some(saved_at)) => format!(
+                "Sentry's settings file was damaged ({}). Your settings were restored from a copy saved {}; changes made after that are lost.",
+                self.error,
+                saved_at.format("%Y-%m-%d %H:%M UTC")
+            ),
+            (Some(_), None) => format!(
+                "Sentry's settings file was damaged ({}). Your settings were restored from a backup copy.",
+                self.error
+            ),
+            (None, _) => format!(
+                "Sentry's settings file was damaged ({}) and no backup copy could be read, so default settings were loaded. The damaged file was kept at {}.",
+                self.error,
+                self.corrupt_copy
+                    .as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_else(|| "its original location".to_string())
+            ),
+        }
+    }
+}
+
 pub struct StateManager {
     data_dir: PathBuf,
     state: AppState,
+    /// Set when `load` had to fall back to a backup copy or the defaults
+    recovery: Option<StateRecovery>,
 }
 
 impl StateManager {
@@ -121,6 +170,7 @@ impl StateManager {
         Self {
             data_dir,
             state: AppState::default(),
+            recovery: None,
         }
     }
 
@@ -128,13 +178,32 @@ impl StateManager {
         self.data_dir.join("app_state.json")
     }
 
+    /// Backup copy `n`, where 1 is the newest
+    fn backup_path(&self, n: usize) -> PathBuf {
+        self.data_dir.join(format!("app_state.json.{}", n))
+    }
+
+    fn read_state(path: &Path) -> Result<AppState, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|e| e.to_string())
+    }
+
+    /// Load the saved state. An unreadable state file is set aside and replaced by the
+    /// newest readable backup copy, or the defaults if there is none; `recovery` then
+    /// says what happened.
     pub fn load(&mut self) -> Result<(), std::io::Error> {
         let path = self.state_path();
-        if path.exists() {
-            let file = File::open(&path)?;
-            let reader = BufReader::new(file);
-            self.state = serde_json::from_reader(reader)
-                .unwrap_or_else(|_| AppState::default());
+        let has_backups = (1..=STATE_BACKUPS).any(|n| self.backup_path(n).exists());
+        if path.exists() || has_backups {
+            let loaded = if path.exists() {
+                Self::read_state(&path)
+            } else {
+                Err("the file is missing".to_string())
+            };
+            match loaded {
+                Ok(state) => self.state = state,
+                Err(error) => self.recover(error)?,
+            }
             self.state.first_run = false;
         }
 
@@ -148,14 +217,86 @@ impl StateManager {
         Ok(())
     }
 
+    fn recover(&mut self, error: String) -> Result<(), std::io::Error> {
+        eprintln!("Failed to read app state: {}", error);
+
+        let path = self.state_path();
+        let corrupt_copy = if path.exists() {
+            let target = self.data_dir.join(format!(
+                "app_state.corrupt-{}.json",
+                Utc::now().format("%Y%m%d%H%M%S")
+            ));
+            fs::rename(&path, &target)?;
+            Some(target)
+        } else {
+            None
+        };
+
+        let restored = (1..=STATE_BACKUPS).find_map(|n| {
+            let backup = self.backup_path(n);
+            match Self::read_state(&backup) {
+                Ok(state) => Some((backup, state)),
+                Err(e) => {
+                    if backup.exists() {
+                        eprintln!("Failed to read state backup {}: {}", backup.display(), e);
+                    }
+                    None
+                }
+            }
+        });
+
+        let recovery = match restored {
+            Some((backup, state)) => {
+                println!("Restored app state from {}", backup.display());
+                let restored_state_time = Some(state.updated_at);
+                self.state = state;
+                // Write the primary right away; backup copies are left as they are
+                write_json_atomic(&path, &self.state)?;
+                StateRecovery {
+                    error,
+                    corrupt_copy,
+                    restored_from: Some(backup),
+                    restored_state_time,
+                }
+            }
+            None => {
+                self.state = AppState::default();
+                StateRecovery {
+                    error,
+                    corrupt_copy,
+                    restored_from: None,
+                    restored_state_time: None,
+                }
+            }
+        };
+        self.recovery = Some(recovery);
+        Ok(())
+    }
+
+    /// Recovery performed by the last `load`, if any.
+    pub fn recovery(&self) -> Option<&StateRecovery> {
+        self.recovery.as_ref()
+    }
+
+    /// Write the state atomically, first copying the current file into the rotation of
+    /// backup copies.
     pub fn save(&mut self) -> Result<(), std::io::Error> {
         fs::create_dir_all(&self.data_dir)?;
         let path = self.state_path();
-        let file = File::create(&path)?;
-        let writer = BufWriter::new(file);
+
+        if path.exists() {
+            let _ = fs::remove_file(self.backup_path(STATE_BACKUPS));
+            for n in (1..STATE_BACKUPS).rev() {
+                let from = self.backup_path(n);
+                if from.exists() {
+                    fs::rename(&from, self.backup_path(n + 1))?;
+                }
+            }
+            fs::copy(&path, self.backup_path(1))?;
+        }
+
         self.state.updated_at = Utc::now();
-        serde_json::to_writer_pretty(writer, &self.state)?;
-        Ok(())
+        write_json_atomic(&path, &self.state)
     }
 
     pub fn get_state(&self) -> &AppState {
@@ -238,7 +379,12 @@ impl StateManager {
         schedule.calculate_next_run();
         schedule.updated_at = Utc::now();
 
-        if let Some(existing) = self.state.schedules.iter_mut().find(|s| s.id == schedule.id) {
+        if let Some(existing) = self
+            .state
+            .schedules
+            .iter_mut()
+            .find(|s| s.id == schedule.id)
+        {
             *existing = schedule;
         } else {
             // If the schedule was not found, append it to avoid dropping the update
@@ -260,4 +406,3 @@ impl StateManager {
         self.state.onboarding.completed
     }
 }
-
@@ -104,6 +104,12 @@ function App() {
         // Check the correct field: onboarding.completed
         const isOnboardingComplete = result.data.onboarding?.completed === true
         setShowOnboarding(!isOnboardingComplete)
+
+        // Tell the user if saved settings had to be restored from a backup copy
+        const recovery = await api.getStateRecovery()
+        if (recovery.success && recovery.data) {
+          alert(recovery.data)
+        }
       } else {
         // No data or error, show onboarding
         setShowOnboarding(true)
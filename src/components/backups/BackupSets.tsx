@@ -15,7 +15,9 @@ import {
   Loader2,
   Files,
   Edit,
-  AlertTriangle
+  AlertTriangle,
+  Download,
+  Upload
 } from 'lucide-react'
 import { Button } from '@/components/ui/button'
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/components/ui/card'
@@ -27,7 +29,7 @@ import { Separator } from '@/components/ui/separator'
 import { useAppStore } from '@/lib/store'
 import { api, events } from '@/lib/tauri'
 import { Progress } from '@/components/ui/progress'
-import type { BackupSet } from '@/lib/tauri'
+import type { BackupSet, ConflictPolicy, ImportReport, ValidationReport } from '@/lib/tauri'
 import { formatBytes, formatRelativeTime } from '@/lib/utils'
 
 // Helper function to determine backup type
@@ -52,13 +54,35 @@ function getBackupTypeDisplay(type: 'local' | 'cloud' | 'both') {
   }
 }
 
+function showValidationWarnings(report: ValidationReport | null) {
+  if (report && report.warnings.length > 0) {
+    alert('Backup set saved with warnings:\n' + report.warnings.map(w => '• ' + w.message).join('\n'))
+  }
+}
+
+function describeRejection(report: ValidationReport | null, error: string | null) {
+  if (report && report.errors.length > 0) {
+    return report.errors.map(e => '• ' + e.message).join('\n')
+  }
+  return error || 'Unknown error'
+}
+
+function describeImport(report: ImportReport) {
+  return report.outcomes
+    .map(o => {
+      const kind = o.kind === 'backup_set' ? 'Backup set' : 'Schedule'
+      return `• ${kind} "${o.name}": ${o.action}${o.message ? ` (${o.message})` : ''}`
+    })
+    .join('\n')
+}
+
 interface FolderStats {
   file_count: number
   total_size: number
 }
 
 export function BackupSets() {
-  const { appState, addBackupSet, updateBackupSet, removeBackupSet, setBackupProgress, currentBackupProgress } = useAppStore()
+  const { appState, setAppState, addBackupSet, updateBackupSet, removeBackupSet, setBackupProgress, currentBackupProgress } = useAppStore()
   const [isCreateOpen, setIsCreateOpen] = useState(false)
   const [editingSetId, setEditingSetId] = useState<string | null>(null)
   const [isRunning, setIsRunning] = useState<string | null>(null)
@@ -70,6 +94,7 @@ export function BackupSets() {
   const [folderStats, setFolderStats] = useState<Record<string, FolderStats>>({})
   const [loadingStats, setLoadingStats] = useState<Record<string, boolean>>({})
   const [noChangePromptSet, setNoChangePromptSet] = useState<BackupSet | null>(null)
+  const [importConflicts, setImportConflicts] = useState<{ path: string; names: string[] } | null>(null)
 
   const backupSets = appState?.backup_sets?.sets || []
 
@@ -78,8 +103,11 @@ export function BackupSets() {
     const unsub = events.onBackupProgress((progress) => {
       setBackupProgress(progress)
 
-      if (progress.status === 'Completed') {
+      if (progress.status === 'Completed' || progress.status === 'CompletedWithWarnings') {
         setIsRunning(null)
+        if (progress.error) {
+          console.warn('Backup completed with warnings:', progress.error)
+        }
         api.getBackupSets().then((result) => {
           if (result.success && result.data) {
             result.data.forEach((set) => updateBackupSet(set.id, set))
@@ -158,21 +186,39 @@ export function BackupSets() {
             local_destination: (backupType === 'local' || backupType === 'both') ? localDestination : null
           }
 
-          await api.updateBackupSet(updatedSet)
+          const saved = await api.updateBackupSet(updatedSet)
+          if (!saved.success) {
+            alert('Could not save backup set:\n' + describeRejection(saved.data, saved.error))
+            return
+          }
+          showValidationWarnings(saved.data)
           updateBackupSet(editingSetId, updatedSet)
         }
       } else {
         // Create new set
         const result = await api.createBackupSet(newSetName, selectedSources)
-        if (result.success && result.data) {
+        if (!result.success) {
+          alert('Could not create backup set:\n' + describeRejection(result.data?.validation ?? null, result.error))
+          return
+        }
+        if (result.data) {
+          const created = result.data.set
           const cloudUpload = backupType === 'cloud' || backupType === 'both'
           const updatedSet = {
-            ...result.data,
+            ...created,
             description: newSetDescription.trim() || null,
             cloud_upload: cloudUpload,
             local_destination: (backupType === 'local' || backupType === 'both') ? localDestination : null
           }
-          await api.updateBackupSet(updatedSet)
+          const saved = await api.updateBackupSet(updatedSet)
+          if (!saved.success) {
+            // The set exists without the rejected destination; keep the dialog open to fix it
+            addBackupSet(created)
+            setEditingSetId(created.id)
+            alert('Could not save backup set:\n' + describeRejection(saved.data, saved.error))
+            return
+          }
+          showValidationWarnings(saved.data)
           addBackupSet(updatedSet)
         }
       }
@@ -219,17 +265,76 @@ export function BackupSets() {
     }
   }
 
+  const handleExportDefinitions = async () => {
+    try {
+      const path = await api.pickDefinitionsExportPath()
+      if (!path) return
+      const result = await api.exportDefinitions(path)
+      if (!result.success) {
+        alert('Export failed: ' + (result.error || 'Unknown error'))
+      }
+    } catch (error) {
+      console.error('Failed to export definitions:', error)
+    }
+  }
+
+  const handleImportDefinitions = async () => {
+    try {
+      const path = await api.pickDefinitionsFile()
+      if (!path) return
+
+      // Preview first so the user can decide what happens to names that already exist
+      const preview = await api.importDefinitions(path, 'skip', true)
+      if (!preview.success || !preview.data) {
+        alert('Import failed: ' + (preview.error || 'Unknown error'))
+        return
+      }
+      const conflicts = preview.data.outcomes.filter(o => o.action === 'skipped' && o.id)
+      if (conflicts.length > 0) {
+        setImportConflicts({ path, names: conflicts.map(o => o.name) })
+        return
+      }
+      await runImport(path, 'skip')
+    } catch (error) {
+      console.error('Failed to import definitions:', error)
+    }
+  }
+
+  const runImport = async (path: string, policy: ConflictPolicy) => {
+    try {
+      const result = await api.importDefinitions(path, policy)
+      if (!result.success || !result.data) {
+        alert('Import failed: ' + (result.error || 'Unknown error'))
+        return
+      }
+      const state = await api.getAppState()
+      if (state.success && state.data) {
+        setAppState(state.data)
+      }
+      alert('Import finished:\n' + describeImport(result.data))
+    } catch (error) {
+      console.error('Failed to import definitions:', error)
+    }
+  }
+
+  const resolveImportConflicts = (policy: ConflictPolicy) => {
+    if (importConflicts) {
+      runImport(importConflicts.path, policy)
+    }
+    setImportConflicts(null)
+  }
+
   const handleRunBackup = async (set: BackupSet, forceFull?: boolean) => {
     console.log('handleRunBackup called for set:', set.id, set.name)
     setIsRunning(set.id)
     try {
-      console.log('Calling api.runBackup for set:', set.id, 'incremental:', set.incremental)
-      const incremental = forceFull ? false : set.incremental
-      const result = await api.runBackup(set.id, incremental)
+      console.log('Calling api.runBackup for set:', set.id, 'mode:', set.mode)
+      const mode = forceFull ? 'full' : set.mode
+      const result = await api.runBackup(set.id, mode)
       console.log('runBackup result:', JSON.stringify(result, null, 2))
       if (result.success && result.data) {
         console.log('Backup successful! Files:', result.data.total_files, 'Bytes:', result.data.total_bytes)
-        if (result.data.total_bytes === 0 && result.data.total_files === 0) {
+        if (result.data.total_bytes === 0 && result.data.total_files === 0 && result.data.files_deleted === 0) {
           if (!forceFull) {
             setNoChangePromptSet(set)
           }
@@ -298,6 +403,16 @@ export function BackupSets() {
             Configure and manage your backup collections
           </p>
         </div>
+        <div className="flex items-center gap-2 ml-auto mr-2">
+          <Button variant="outline" onClick={handleImportDefinitions}>
+            <Upload className="w-4 h-4 mr-2" />
+            Import
+          </Button>
+          <Button variant="outline" onClick={handleExportDefinitions} disabled={backupSets.length === 0}>
+            <Download className="w-4 h-4 mr-2" />
+            Export
+          </Button>
+        </div>
         <Dialog open={isCreateOpen} onOpenChange={setIsCreateOpen}>
           <DialogTrigger asChild>
             <Button onClick={() => {
@@ -691,6 +806,44 @@ export function BackupSets() {
           </DialogFooter>
         </DialogContent>
       </Dialog>
+
+      {/* Import conflicts */}
+      <Dialog
+        open={!!importConflicts}
+        onOpenChange={(open) => !open && setImportConflicts(null)}
+      >
+        <DialogContent className="max-w-md">
+          <DialogHeader>
+            <DialogTitle className="flex items-center gap-2">
+              <AlertTriangle className="w-5 h-5 text-amber-500" />
+              Items already exist
+            </DialogTitle>
+            <DialogDescription>
+              {importConflicts?.names.length} item(s) in this file already exist. Replace them,
+              keep both by importing the new ones under another name, or import only the rest.
+            </DialogDescription>
+          </DialogHeader>
+          <ul className="text-sm space-y-1 max-h-40 overflow-y-auto">
+            {importConflicts?.names.map((name, index) => (
+              <li key={index} className="truncate">• {name}</li>
+            ))}
+          </ul>
+          <DialogFooter className="gap-2 sm:gap-0">
+            <Button variant="outline" onClick={() => setImportConflicts(null)}>
+              Cancel
+            </Button>
+            <Button variant="outline" onClick={() => resolveImportConflicts('skip')}>
+              Skip existing
+            </Button>
+            <Button variant="outline" onClick={() => resolveImportConflicts('rename')}>
+              Keep both
+            </Button>
+            <Button onClick={() => resolveImportConflicts('replace')}>
+              Replace
+            </Button>
+          </DialogFooter>
+        </DialogContent>
+      </Dialog>
     </motion.div>
   )
 }
@@ -11,7 +11,8 @@ import {
   LogOut,
   Loader2,
   CheckCircle2,
-  Key
+  Key,
+  LifeBuoy
 } from 'lucide-react'
 import { Button } from '@/components/ui/button'
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/components/ui/card'
@@ -29,8 +30,9 @@ import {
 import { api, CloudBackupBundle } from '@/lib/tauri'
 import { open } from '@tauri-apps/plugin-shell'
 import { join } from '@tauri-apps/api/path'
-import { formatBytes, formatDate } from '@/lib/utils'
+import { formatBytes, formatDate, formatPath } from '@/lib/utils'
 import { useDownloadStore } from '@/lib/downloads-store'
+import { useAppStore } from '@/lib/store'
 
 export function CloudStorage() {
   const [bundles, setBundles] = useState<CloudBackupBundle[]>([])
@@ -46,6 +48,8 @@ export function CloudStorage() {
   const [isConnecting, setIsConnecting] = useState(false)
   const [connectionError, setConnectionError] = useState<string | null>(null)
   const { startDownload, completeByPath, failByPath, setPanelOpen } = useDownloadStore()
+  const { setAppState } = useAppStore()
+  const [isRecovering, setIsRecovering] = useState(false)
 
   useEffect(() => {
     checkAuth()
@@ -135,14 +139,47 @@ export function CloudStorage() {
     }
   }
 
-  const handleDeleteBundle = async (bundle: CloudBackupBundle) => {
+  const handleRecover = async () => {
+    if (!confirm('Copy the records of every backup in Google Drive to this computer? Backup sets that no longer exist here are recreated, disabled, so their versions can be restored.')) {
+      return
+    }
+    setIsRecovering(true)
     try {
-      const manifestDelete = await api.deleteFromDrive(bundle.manifest_file.id)
-      const archiveDelete = await api.deleteFromDrive(bundle.archive_file.id)
+      const result = await api.recoverFromCloud()
+      if (!result.success || !result.data) {
+        alert('Recovery failed: ' + (result.error || 'Unknown error'))
+      } else {
+        const report = result.data
+        const state = await api.getAppState()
+        if (state.success && state.data) {
+          setAppState(state.data)
+        }
+        const lines = [
+          `Found ${report.manifests_found} backups, ${report.manifests_imported} new to this computer.`,
+          ...report.backup_sets_created.map(set => `• Recreated backup set "${set.name}"`),
+          ...report.failures.map(f => `• Could not read ${f.name}: ${f.error}`),
+        ]
+        alert(lines.join('\n'))
+      }
+    } catch (error) {
+      console.error('Failed to recover from cloud:', error)
+    }
+    setIsRecovering(false)
+  }
 
-      if (manifestDelete.success && archiveDelete.success) {
-        setBundles(bundles.filter(b => b.manifest.id !== bundle.manifest.id))
+  const handleDeleteBundle = async (bundle: CloudBackupBundle) => {
+    try {
+      // The manifest goes last so a failed delete never leaves volumes it no longer lists
+      const archiveFiles = bundle.volumes.length > 0 ? bundle.volumes : [bundle.archive_file]
+      for (const file of [...archiveFiles, bundle.manifest_file]) {
+        const result = await api.deleteFromDrive(file.id)
+        if (!result.success) {
+          alert(`Could not delete ${file.name}: ${result.error || 'Unknown error'}`)
+          return
+        }
       }
+
+      setBundles(bundles.filter(b => b.manifest.id !== bundle.manifest.id))
     } catch (error) {
       console.error('Failed to delete file:', error)
     }
@@ -352,6 +389,14 @@ export function CloudStorage() {
           </p>
         </div>
         <div className="flex items-center gap-2">
+          <Button variant="outline" onClick={handleRecover} disabled={isRecovering}>
+            {isRecovering ? (
+              <Loader2 className="w-4 h-4 mr-2 animate-spin" />
+            ) : (
+              <LifeBuoy className="w-4 h-4 mr-2" />
+            )}
+            Recover from Cloud
+          </Button>
           <Button variant="outline" onClick={loadData} disabled={isLoading}>
             <RefreshCw className={`w-4 h-4 mr-2 ${isLoading ? 'animate-spin' : ''}`} />
             Refresh
@@ -583,7 +628,7 @@ export function CloudStorage() {
                   </div>
                   <div className="divide-y text-sm">
                     {selectedBundle.manifest.files.map((file) => {
-                      const path = file.relative_path || file.path
+                      const path = formatPath(file.relative_path || file.path)
                       return (
                         <div key={path} className="grid grid-cols-12 py-2 items-start">
                           <div className="col-span-6 pr-2 break-all">{path}</div>
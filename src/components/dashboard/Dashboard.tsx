@@ -18,7 +18,7 @@ import { Checkbox } from '@/components/ui/checkbox'
 import { Progress } from '@/components/ui/progress'
 import { useAppStore, useUIStore } from '@/lib/store'
 import { api } from '@/lib/tauri'
-import type { BackupSet } from '@/lib/tauri'
+import type { BackupSet, ProtectionStatus } from '@/lib/tauri'
 import { formatBytes, formatRelativeTime } from '@/lib/utils'
 
 export function Dashboard() {
@@ -29,6 +29,7 @@ export function Dashboard() {
   const [noChangePromptSet, setNoChangePromptSet] = useState<BackupSet | null>(null)
   const [showBackupModal, setShowBackupModal] = useState(false)
   const [selectedSetIds, setSelectedSetIds] = useState<string[]>([])
+  const [protection, setProtection] = useState<ProtectionStatus[]>([])
 
   const backupSets = appState?.backup_sets?.sets ?? []
   const schedules = appState?.schedules ?? []
@@ -52,6 +53,35 @@ export function Dashboard() {
     }
   }, [appState?.location])
 
+  // Re-check what is unprotected whenever a set finishes a backup
+  const protectionKey = enabledSets.map((s) => `${s.id}:${s.last_backup ?? ''}`).join(',')
+  useEffect(() => {
+    let cancelled = false
+    const fetchProtection = async () => {
+      const statuses: ProtectionStatus[] = []
+      for (const set of enabledSets) {
+        try {
+          const result = await api.getProtectionStatus(set.id)
+          if (result.success && result.data) {
+            statuses.push(result.data)
+          }
+        } catch (error) {
+          console.error('Failed to check protection status:', error)
+        }
+      }
+      if (!cancelled) setProtection(statuses)
+    }
+
+    fetchProtection()
+    return () => {
+      cancelled = true
+    }
+  }, [protectionKey])
+
+  const atRisk = protection.filter(
+    (p) => p.new_files.length + p.changed_files.length + p.deleted_files.length > 0
+  )
+
   // Keep selected sets in sync with enabled sets
   useEffect(() => {
     const enabledIds = enabledSets.map((s) => s.id)
@@ -82,10 +112,10 @@ export function Dashboard() {
   const runBackupForSet = async (set: BackupSet, forceFull = true, manageRunning = true) => {
     if (manageRunning) setIsRunningBackup(true)
     try {
-      const incremental = forceFull ? false : set.incremental
-      const result = await api.runBackup(set.id, incremental)
+      const mode = forceFull ? 'full' : set.mode
+      const result = await api.runBackup(set.id, mode)
       if (result.success && result.data) {
-        if (result.data.total_bytes === 0 && result.data.total_files === 0) {
+        if (result.data.total_bytes === 0 && result.data.total_files === 0 && result.data.files_deleted === 0) {
           if (!forceFull) {
             setNoChangePromptSet(set)
           }
@@ -166,7 +196,9 @@ export function Dashboard() {
               </div>
             </div>
 
-            {currentBackupProgress && currentBackupProgress.status !== 'Completed' ? (
+            {currentBackupProgress &&
+            currentBackupProgress.status !== 'Completed' &&
+            currentBackupProgress.status !== 'CompletedWithWarnings' ? (
               <motion.div
                 initial={{ opacity: 0, y: 4 }}
                 animate={{ opacity: 1, y: 0 }}
@@ -205,6 +237,58 @@ export function Dashboard() {
         </Card>
       </motion.div>
 
+      {/* Unprotected changes */}
+      {atRisk.length > 0 && (
+        <motion.div variants={item}>
+          <Card className="border-amber-300 dark:border-amber-800 bg-amber-50 dark:bg-amber-900/20">
+            <CardHeader>
+              <CardTitle className="flex items-center gap-2 text-amber-800 dark:text-amber-200">
+                <AlertTriangle className="w-5 h-5 text-amber-500" />
+                Changes not yet backed up
+              </CardTitle>
+              <CardDescription>
+                {formatBytes(atRisk.reduce((acc, p) => acc + p.bytes_at_risk, 0))} would be lost if
+                your disk failed now
+              </CardDescription>
+            </CardHeader>
+            <CardContent className="space-y-2">
+              {atRisk.map((status) => {
+                const set = backupSets.find((s) => s.id === status.backup_set_id)
+                return (
+                  <div
+                    key={status.backup_set_id}
+                    className="flex items-center justify-between gap-3 p-3 rounded-lg bg-background/60"
+                  >
+                    <div>
+                      <p className="font-medium">{set?.name ?? status.backup_set_id}</p>
+                      <p className="text-sm text-muted-foreground">
+                        {status.new_files.length} new, {status.changed_files.length} changed,{' '}
+                        {status.deleted_files.length} deleted
+                        {status.oldest_unprotected_change &&
+                          ` • oldest change ${formatRelativeTime(status.oldest_unprotected_change)}`}
+                      </p>
+                    </div>
+                    <div className="flex items-center gap-3">
+                      <span className="text-sm font-medium">{formatBytes(status.bytes_at_risk)}</span>
+                      {set && (
+                        <Button
+                          size="sm"
+                          variant="outline"
+                          disabled={isRunningBackup}
+                          onClick={() => runBackupForSet(set, false)}
+                        >
+                          Back up
+                        </Button>
+                      )}
+                    </div>
+                  </div>
+                )
+              })}
+            </CardContent>
+          </Card>
+        </motion.div>
+      )}
+
       {/* Stats grid */}
       <motion.div variants={item} className="grid grid-cols-1 md:grid-cols-2 lg:grid-cols-4 gap-4">
         <Card>
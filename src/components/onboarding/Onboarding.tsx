@@ -180,7 +180,9 @@ export function Onboarding({ onComplete }: OnboardingProps) {
       for (const preset of selectedPresets) {
         const result = await api.createBackupSetFromPreset(preset, homeDir)
         if (result.success && result.data) {
-          addBackupSet(result.data)
+          addBackupSet(result.data.set)
+        } else {
+          console.error(`Skipped ${preset} backup set:`, result.error)
         }
       }
       handleNext()
@@ -409,6 +409,9 @@ export function Schedule() {
                               {schedule.schedule_type === 'monthly' && (
                                 <span className="ml-1">(Day {schedule.day_of_month})</span>
                               )}
+                              {schedule.mode && (
+                                <span className="ml-1">· {schedule.mode} backups</span>
+                              )}
                             </span>
                           </div>
                           <div className="flex items-center gap-2 text-muted-foreground">
@@ -11,7 +11,9 @@ import {
   Loader2,
   Check,
   RefreshCw,
-  AlertCircle
+  AlertCircle,
+  Database,
+  HardDrive
 } from 'lucide-react'
 import { Button } from '@/components/ui/button'
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/components/ui/card'
@@ -24,13 +26,15 @@ import { check as checkForUpdate, type DownloadEvent } from '@tauri-apps/plugin-
 import { relaunch } from '@tauri-apps/plugin-process'
 
 export function Settings() {
-  const { appState, updateSettings, setLocation } = useAppStore()
+  const { appState, setAppState, updateSettings, setLocation } = useAppStore()
   const { theme, setTheme } = useUIStore()
   const [isDetectingLocation, setIsDetectingLocation] = useState(false)
   const [, setIsSaving] = useState(false)
   const [updateStatus, setUpdateStatus] = useState<'idle' | 'disabled' | 'checking' | 'none' | 'available' | 'downloading' | 'installed' | 'error'>('disabled')
   const [updateProgress, setUpdateProgress] = useState<number>(0)
   const [updateMessage, setUpdateMessage] = useState<string | null>(null)
+  const [isRebuildingIndex, setIsRebuildingIndex] = useState(false)
+  const [isImportingFolder, setIsImportingFolder] = useState(false)
 
   const settings = appState?.settings
   const location = appState?.location
@@ -73,6 +77,55 @@ export function Settings() {
     setIsDetectingLocation(false)
   }
 
+  const handleRebuildIndex = async () => {
+    setIsRebuildingIndex(true)
+    try {
+      const result = await api.rebuildManifestIndex()
+      if (result.success && result.data) {
+        const { manifests, unreadable } = result.data
+        alert(
+          `Catalog rebuilt from ${manifests} backup manifest${manifests !== 1 ? 's' : ''}.` +
+            (unreadable.length > 0
+              ? ` ${unreadable.length} unreadable file${unreadable.length !== 1 ? 's were' : ' was'} skipped.`
+              : '')
+        )
+      } else {
+        alert(result.error || 'Failed to rebuild the backup catalog')
+      }
+    } catch (error) {
+      console.error('Failed to rebuild manifest index:', error)
+    }
+    setIsRebuildingIndex(false)
+  }
+
+  const handleImportFolder = async () => {
+    const folder = await api.pickDirectory()
+    if (!folder) return
+
+    setIsImportingFolder(true)
+    try {
+      const result = await api.recoverFromFolder(folder)
+      if (result.success && result.data) {
+        const report = result.data
+        const state = await api.getAppState()
+        if (state.success && state.data) {
+          setAppState(state.data)
+        }
+        const lines = [
+          `Found ${report.manifests_found} backup archive${report.manifests_found !== 1 ? 's' : ''}, ${report.manifests_imported} new to this computer.`,
+          ...report.backup_sets_created.map(set => `• Recreated backup set "${set.name}"`),
+          ...report.failures.map(f => `• Could not read ${f.name}: ${f.error}`),
+        ]
+        alert(lines.join('\n'))
+      } else {
+        alert(result.error || 'Failed to import the backup folder')
+      }
+    } catch (error) {
+      console.error('Failed to import backup folder:', error)
+    }
+    setIsImportingFolder(false)
+  }
+
   const handleCheckForUpdates = async () => {
     try {
       setUpdateStatus('checking')
@@ -376,6 +429,19 @@ export function Settings() {
                 onCheckedChange={(checked) => handleSettingChange('minimize_to_tray', checked)}
               />
             </div>
+            <Separator />
+            <div className="flex items-center justify-between">
+              <div>
+                <Label>Stream uploads</Label>
+                <p className="text-sm text-muted-foreground">
+                  Send cloud-only backups straight to Google Drive without a full local copy
+                </p>
+              </div>
+              <Switch
+                checked={settings?.stream_uploads ?? false}
+                onCheckedChange={(checked) => handleSettingChange('stream_uploads', checked)}
+              />
+            </div>
           </CardContent>
         </Card>
       </motion.div>
@@ -432,6 +498,50 @@ export function Settings() {
               </Button>
             </div>
             <Separator />
+            <div className="flex items-center justify-between">
+              <div>
+                <Label>Rebuild Backup Catalog</Label>
+                <p className="text-sm text-muted-foreground">
+                  Re-read every backup manifest if versions are missing from the list
+                </p>
+              </div>
+              <Button
+                variant="outline"
+                size="sm"
+                onClick={handleRebuildIndex}
+                disabled={isRebuildingIndex}
+              >
+                {isRebuildingIndex ? (
+                  <Loader2 className="w-4 h-4 mr-2 animate-spin" />
+                ) : (
+                  <Database className="w-4 h-4 mr-2" />
+                )}
+                Rebuild
+              </Button>
+            </div>
+            <Separator />
+            <div className="flex items-center justify-between">
+              <div>
+                <Label>Import Backup Folder</Label>
+                <p className="text-sm text-muted-foreground">
+                  Add the archives on a backup disk or folder to the catalog, e.g. on a new computer
+                </p>
+              </div>
+              <Button
+                variant="outline"
+                size="sm"
+                onClick={handleImportFolder}
+                disabled={isImportingFolder}
+              >
+                {isImportingFolder ? (
+                  <Loader2 className="w-4 h-4 mr-2 animate-spin" />
+                ) : (
+                  <HardDrive className="w-4 h-4 mr-2" />
+                )}
+                Import
+              </Button>
+            </div>
+            <Separator />
             <div className="space-y-3">
               <div className="flex items-center justify-between">
                 <div>
@@ -15,7 +15,7 @@ export async function runBackupForSet(setId: string) {
     throw new Error('Backup set not found')
   }
 
-  const result = await api.runBackup(setId, backupSet.incremental)
+  const result = await api.runBackup(setId, backupSet.mode)
 
   if (!result.success) {
     throw new Error(result.error ?? 'Backup failed')
@@ -23,7 +23,7 @@ export async function runBackupForSet(setId: string) {
 
   if (result.data) {
     // If nothing changed, skip stat increments to avoid misleading counters
-    if (result.data.total_bytes === 0 && result.data.total_files === 0) {
+    if (result.data.total_bytes === 0 && result.data.total_files === 0 && result.data.files_deleted === 0) {
       return result
     }
 
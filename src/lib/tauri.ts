@@ -21,6 +21,7 @@ export interface AppSettings {
   backup_check_interval_minutes: number
   max_concurrent_uploads: number
   chunk_size_mb: number
+  stream_uploads: boolean
 }
 
 export interface OnboardingState {
@@ -39,6 +40,27 @@ export interface DriveConfig {
   backup_folder_name: string
 }
 
+export type BackupMode = 'full' | 'incremental' | 'differential'
+
+export interface GfsPolicy {
+  daily: number
+  weekly: number
+  monthly: number
+  yearly: number
+}
+
+export type FileErrorPolicy =
+  | 'skip'
+  | 'fail'
+  | { retry: { attempts: number; backoff_ms: number } }
+
+export interface SkippedFile {
+  path: StoredPath
+  reason: string
+  stage: 'scan' | 'archive'
+  attempts: number
+}
+
 export interface BackupSet {
   id: string
   name: string
@@ -48,9 +70,11 @@ export interface BackupSet {
   exclude_patterns: string[]
   enabled: boolean
   compression_level: number
-  incremental: boolean
+  mode: BackupMode
   retention_days: number | null
   max_versions: number | null
+  gfs_policy: GfsPolicy | null
+  file_error_policy: FileErrorPolicy
   cloud_upload: boolean
   local_destination: string | null
   created_at: string
@@ -60,6 +84,31 @@ export interface BackupSet {
   total_size_backed_up: number
 }
 
+export type ValidationCode =
+  | 'no_sources'
+  | 'destination_in_source'
+  | 'source_missing'
+  | 'source_not_directory'
+  | 'duplicate_source'
+  | 'nested_source'
+
+export interface ValidationIssue {
+  code: ValidationCode
+  path: string | null
+  related_path: string | null
+  message: string
+}
+
+export interface ValidationReport {
+  errors: ValidationIssue[]
+  warnings: ValidationIssue[]
+}
+
+export interface SavedBackupSet {
+  set: BackupSet
+  validation: ValidationReport
+}
+
 export interface Schedule {
   id: string
   name: string
@@ -74,6 +123,8 @@ export interface Schedule {
   weather_triggers?: WeatherTrigger[]
   last_run?: string | null
   next_run?: string | null
+  /** Mode of scheduled runs; unset follows the backup set */
+  mode?: BackupMode | null
   created_at?: string
   updated_at?: string
 }
@@ -123,10 +174,68 @@ export interface BackupResult {
   backup_set_id: string
   started_at: string
   completed_at: string
+  mode: BackupMode
   total_files: number
   total_bytes: number
   compressed_bytes: number
+  files_deleted: number
+  skipped_files: SkippedFile[]
+  archive_path: string
+  streamed: StreamedArchive | null
+}
+
+export type RunStatus = 'running' | 'completed' | 'completed_with_warnings' | 'no_changes' | 'failed' | 'interrupted'
+
+export interface BackupRun {
+  id: string
+  backup_set_id: string
+  backup_set_name: string | null
+  trigger: 'manual' | 'schedule' | string
+  mode: BackupMode
+  started_at: string
+  finished_at: string | null
+  status: RunStatus
+  files_backed_up: number
+  files_skipped: number
+  files_deleted: number
+  total_bytes: number
+  compressed_bytes: number
+  upload: {
+    status: 'not_requested' | 'uploaded' | 'skipped' | 'failed'
+    volumes: number
+    message: string | null
+  }
+  errors: string[]
+  manifest_id: string | null
+}
+
+export interface BackupRunPage {
+  runs: BackupRun[]
+  total: number
+  offset: number
+  limit: number
+}
+
+export interface RestoreResult {
+  manifest_id: string
+  target: string | null
+  files_restored: number
+  bytes_restored: number
+}
+
+export interface ArchiveVerification {
   archive_path: string
+  manifest_id: string
+  backup_set_name: string
+  files_checked: number
+  bytes_checked: number
+  missing: string[]
+  corrupt: string[]
+}
+
+export interface StreamedArchive {
+  size: number
+  hash: string
 }
 
 export interface DriveFile {
@@ -161,30 +270,243 @@ export interface WeatherConditions {
   icon: string | null
 }
 
+/** Paths that are not valid UTF-8 are sent as their raw bytes */
+export type StoredPath = string | { raw: number[] }
+
 export interface FileEntry {
-  path: string
-  relative_path: string
+  path: StoredPath
+  relative_path: StoredPath
   size: number
   hash: string
   modified: string
   backed_up_at: string | null
+  source_label: string | null
+}
+
+export interface SourceLabel {
+  label: string
+  path: StoredPath
 }
 
 export interface BackupManifest {
+  schema_version: number
   id: string
   backup_set_id: string
   created_at: string
+  mode: BackupMode
+  parent_id: string | null
   files: FileEntry[]
   total_size: number
   compressed_size: number
   cloud_location: any | null
   retention_until: string | null
+  archive_path: string | null
+  merged_from: string[]
+  superseded_by: string | null
+  pinned: boolean
+  label: string | null
+  skipped_files: SkippedFile[]
+  sources: SourceLabel[]
+  deleted_files: { path: StoredPath }[]
+}
+
+export interface ManifestSummary {
+  id: string
+  backup_set_id: string
+  created_at: string
+  mode: BackupMode
+  parent_id: string | null
+  file_count: number
+  total_size: number
+  compressed_size: number
+  is_uploaded: boolean
+  superseded_by: string | null
+  pinned: boolean
+  label: string | null
+}
+
+export interface VersionInfo {
+  summary: ManifestSummary
+  retention_until: string | null
+  archive_path: string | null
+  archive_available: boolean
+  cloud_location: any | null
+  sources: SourceLabel[]
+  skipped_files: SkippedFile[]
+  merged_from: string[]
+  chain: ManifestSummary[]
+  dependents: string[]
+}
+
+export interface ManifestFilePage {
+  files: FileEntry[]
+  total: number
+  offset: number
+  limit: number
+}
+
+export interface ManifestDetail {
+  version: VersionInfo
+  files: ManifestFilePage
+}
+
+export interface FileSearchQuery {
+  name?: string
+  glob?: string
+  path?: string
+  min_size?: number
+  max_size?: number
+  modified_after?: string
+  modified_before?: string
+  backed_up_after?: string
+  backed_up_before?: string
+  backup_set_id?: string
+  hash?: string
+  limit?: number
+}
+
+export interface FileVersion {
+  manifest_id: string
+  backup_set_id: string
+  backed_up_at: string
+  mode: BackupMode
+  origin: 'local' | 'cloud'
+  size: number
+  hash: string
+  modified: string
+  archived_path: StoredPath
+  archive_path: string | null
+  archive_available: boolean
+  cloud_location: any | null
+}
+
+export interface FileHistory {
+  path: StoredPath
+  versions: FileVersion[]
+}
+
+export interface FileSearchResult {
+  files: FileHistory[]
+  manifests_searched: number
+  truncated: boolean
+}
+
+export interface DiffEntry {
+  path: StoredPath
+  size: number
+  hash: string
+}
+
+export interface ManifestDiff {
+  from_id: string
+  to_id: string
+  added: DiffEntry[]
+  removed: DiffEntry[]
+  modified: {
+    path: StoredPath
+    old_size: number
+    new_size: number
+    size_delta: number
+    old_hash: string
+    new_hash: string
+  }[]
+  renamed: { from: StoredPath; to: StoredPath; size: number; hash: string }[]
+  unchanged: number
+  from_files: number
+  to_files: number
+  from_bytes: number
+  to_bytes: number
+  size_delta: number
+}
+
+export interface PendingChange {
+  path: StoredPath
+  size: number
+  modified: string | null
+}
+
+export interface ProtectionStatus {
+  backup_set_id: string
+  checked_at: string
+  last_backup_id: string | null
+  last_backup_at: string | null
+  new_files: PendingChange[]
+  changed_files: PendingChange[]
+  deleted_files: PendingChange[]
+  bytes_at_risk: number
+  oldest_unprotected_change: string | null
+  skipped_files: SkippedFile[]
+}
+
+export interface IndexRebuild {
+  manifests: number
+  unreadable: { path: string; error: string }[]
+}
+
+export interface RecoveryReport {
+  manifests_found: number
+  manifests_imported: number
+  failures: { name: string; error: string }[]
+  index: IndexRebuild
+  backup_sets_created: BackupSet[]
+}
+
+export type ConflictPolicy = 'skip' | 'rename' | 'replace'
+
+export interface ImportOutcome {
+  kind: 'backup_set' | 'schedule'
+  name: string
+  action: 'created' | 'renamed' | 'replaced' | 'skipped'
+  id: string | null
+  message: string | null
+}
+
+export interface ImportReport {
+  outcomes: ImportOutcome[]
+}
+
+export type KeepReason =
+  | 'pinned'
+  | 'latest'
+  | 'within_max_versions'
+  | 'daily'
+  | 'weekly'
+  | 'monthly'
+  | 'yearly'
+  | 'required_by_chain'
+
+export type PruneReason = 'expired' | 'exceeds_max_versions' | 'superseded'
+
+export interface RetentionPlan {
+  backup_set_id: string
+  keep: { manifest: ManifestSummary; reasons: KeepReason[] }[]
+  prune: { manifest: ManifestSummary; reason: PruneReason }[]
+}
+
+export interface VolumeSpace {
+  role: 'temp' | 'destination'
+  path: string
+  required_bytes: number
+  available_bytes: number | null
+}
+
+export interface SizeEstimate {
+  backup_set_id: string
+  mode: BackupMode
+  file_count: number
+  source_bytes: number
+  compression_ratio: number
+  ratio_from_history: boolean
+  estimated_archive_bytes: number
+  volumes: VolumeSpace[]
+  sufficient: boolean
 }
 
 export interface CloudBackupBundle {
   manifest: BackupManifest
   manifest_file: DriveFile
   archive_file: DriveFile
+  volumes: DriveFile[]
 }
 
 export interface DownloadProgressEvent {
@@ -199,6 +521,7 @@ export interface DownloadProgressEvent {
 export const api = {
   // App state
   getAppState: () => invoke<CommandResult<AppState>>('get_app_state'),
+  getStateRecovery: () => invoke<CommandResult<string | null>>('get_state_recovery'),
   isFirstRun: () => invoke<boolean>('is_first_run'),
   updateSettings: (settings: AppSettings) =>
     invoke<CommandResult<void>>('update_settings', { settings }),
@@ -211,13 +534,19 @@ export const api = {
   getBackupSets: () => invoke<CommandResult<BackupSet[]>>('get_backup_sets'),
   getBackupSet: (id: string) => invoke<CommandResult<BackupSet | null>>('get_backup_set', { id }),
   createBackupSet: (name: string, sources: string[]) =>
-    invoke<CommandResult<BackupSet>>('create_backup_set', { name, sources }),
+    invoke<CommandResult<SavedBackupSet>>('create_backup_set', { name, sources }),
   createBackupSetFromPreset: (preset: string, homeDir: string) =>
-    invoke<CommandResult<BackupSet>>('create_backup_set_from_preset', { preset, homeDir }),
+    invoke<CommandResult<SavedBackupSet>>('create_backup_set_from_preset', { preset, homeDir }),
   updateBackupSet: (set: BackupSet) =>
-    invoke<CommandResult<void>>('update_backup_set', { set }),
+    invoke<CommandResult<ValidationReport>>('update_backup_set', { set }),
+  validateBackupSet: (set: BackupSet) =>
+    invoke<CommandResult<ValidationReport>>('validate_backup_set', { set }),
   deleteBackupSet: (id: string) =>
     invoke<CommandResult<void>>('delete_backup_set', { id }),
+  exportDefinitions: (path: string, backupSetIds?: string[]) =>
+    invoke<CommandResult<void>>('export_definitions', { path, backupSetIds }),
+  importDefinitions: (path: string, conflictPolicy?: ConflictPolicy, dryRun?: boolean) =>
+    invoke<CommandResult<ImportReport>>('import_definitions', { path, conflictPolicy, dryRun }),
 
   // Schedules
   listSchedules: () => invoke<CommandResult<Schedule[]>>('get_schedules'),
@@ -245,8 +574,20 @@ export const api = {
     invoke<CommandResult<void>>('set_weather_triggers', { scheduleId, triggers }),
 
   // Backup execution
-  runBackup: (backupSetId: string, incremental: boolean) =>
-    invoke<CommandResult<BackupResult>>('run_backup', { backupSetId, incremental }),
+  runBackup: (backupSetId: string, mode?: BackupMode) =>
+    invoke<CommandResult<BackupResult>>('run_backup', { backupSetId, mode }),
+  listBackupRuns: (backupSetId?: string, offset?: number, limit?: number) =>
+    invoke<CommandResult<BackupRunPage>>('list_backup_runs', { backupSetId, offset, limit }),
+  getBackupRun: (runId: string) =>
+    invoke<CommandResult<BackupRun>>('get_backup_run', { runId }),
+  synthesizeFullBackup: (backupSetId: string) =>
+    invoke<CommandResult<BackupResult>>('synthesize_full_backup', { backupSetId }),
+  previewRetention: (backupSetId: string, policy?: GfsPolicy) =>
+    invoke<CommandResult<RetentionPlan>>('preview_retention', { backupSetId, policy }),
+  estimateBackup: (backupSetId: string, mode?: BackupMode) =>
+    invoke<CommandResult<SizeEstimate>>('estimate_backup', { backupSetId, mode }),
+  getProtectionStatus: (backupSetId: string) =>
+    invoke<CommandResult<ProtectionStatus>>('get_protection_status', { backupSetId }),
 
   // Google Drive
   getGoogleAuthUrl: (clientId?: string, clientSecret?: string) =>
@@ -279,6 +620,8 @@ export const api = {
       archiveFileName: params.archiveFileName,
       outputDir: params.outputDir
     }),
+  recoverFromCloud: () =>
+    invoke<CommandResult<RecoveryReport>>('recover_from_cloud'),
   getDriveQuota: () => invoke<CommandResult<[number, number]>>('get_drive_quota'),
   disconnectGoogle: () => invoke<CommandResult<void>>('disconnect_google'),
 
@@ -289,11 +632,56 @@ export const api = {
   setLocation: (latitude: number, longitude: number, city?: string, stateName?: string) =>
     invoke<CommandResult<void>>('set_location', { latitude, longitude, city, state_name: stateName }),
 
+  // Manifests
+  getManifestsForSet: (backupSetId: string) =>
+    invoke<CommandResult<ManifestSummary[]>>('get_manifests_for_set', { backupSetId }),
+  getManifest: (manifestId: string, offset?: number, limit?: number) =>
+    invoke<CommandResult<ManifestDetail>>('get_manifest', { manifestId, offset, limit }),
+  getManifestVersion: (manifestId: string) =>
+    invoke<CommandResult<VersionInfo>>('get_manifest_version', { manifestId }),
+  rebuildManifestIndex: () =>
+    invoke<CommandResult<IndexRebuild>>('rebuild_manifest_index'),
+  recoverFromFolder: (path: string) =>
+    invoke<CommandResult<RecoveryReport>>('recover_from_folder', { path }),
+  searchBackedUpFiles: (query: FileSearchQuery) =>
+    invoke<CommandResult<FileSearchResult>>('search_backed_up_files', { query }),
+  diffManifests: (fromId: string, toId: string) =>
+    invoke<CommandResult<ManifestDiff>>('diff_manifests', { fromId, toId }),
+  pinBackup: (manifestId: string, label?: string) =>
+    invoke<CommandResult<BackupManifest>>('pin_backup', { manifestId, label }),
+  unpinBackup: (manifestId: string) =>
+    invoke<CommandResult<BackupManifest>>('unpin_backup', { manifestId }),
+  /** Omit targetPath to restore to the original locations; sourceTargets is keyed by source label */
+  restoreBackup: (
+    manifestId: string,
+    targetPath?: string,
+    sourceTargets?: Record<string, string>
+  ) =>
+    invoke<CommandResult<RestoreResult>>('restore_backup', {
+      manifestId,
+      targetPath,
+      sourceTargets,
+    }),
+  restoreFromArchive: (
+    archivePath: string,
+    targetPath?: string,
+    sourceTargets?: Record<string, string>
+  ) =>
+    invoke<CommandResult<RestoreResult>>('restore_from_archive', {
+      archivePath,
+      targetPath,
+      sourceTargets,
+    }),
+  verifyArchive: (archivePath: string) =>
+    invoke<CommandResult<ArchiveVerification>>('verify_archive', { archivePath }),
+
   // System
   getHomeDirectory: () => invoke<string>('get_home_directory'),
   getDocumentsDirectory: () => invoke<string>('get_documents_directory'),
   pickDirectory: () => invoke<string | null>('pick_directory'),
   pickDirectories: () => invoke<string[]>('pick_directories'),
+  pickDefinitionsFile: () => invoke<string | null>('pick_definitions_file'),
+  pickDefinitionsExportPath: () => invoke<string | null>('pick_definitions_export_path'),
   getFolderStats: (paths: string[]) =>
     invoke<CommandResult<{ file_count: number; total_size: number }>>('get_folder_stats', { paths }),
 }
@@ -28,6 +28,10 @@ export function formatDate(date: Date | string): string {
   })
 }
 
+export function formatPath(path: string | { raw: number[] }): string {
+  return typeof path === 'string' ? path : new TextDecoder().decode(new Uint8Array(path.raw))
+}
+
 export function formatRelativeTime(date: Date | string): string {
   const d = typeof date === 'string' ? new Date(date) : date
   const now = new Date()